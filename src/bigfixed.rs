@@ -0,0 +1,207 @@
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+
+use num::bigint::BigInt;
+use num::traits::{Float, Signed, ToPrimitive, Zero};
+
+/// Extra fractional bits kept on top of what the zoom level strictly needs,
+/// enough to resolve individual pixels and absorb rounding in the iteration.
+const GUARD_BITS: usize = 64;
+
+/// Signed binary fixed-point number of arbitrary precision.
+///
+/// The value is `mantissa / 2^frac_bits`. Fixed point is a good fit for fractal
+/// coordinates: they never grow large, only the number of fractional digits
+/// needed to tell neighbouring pixels apart keeps growing with the zoom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BigFixed {
+    mantissa: BigInt,
+    frac_bits: usize,
+}
+
+impl BigFixed {
+    /// Number of fractional bits needed to render a view at `zoom` without losing pixels.
+    pub fn precision_for_zoom(zoom: f64) -> usize {
+        let bits = zoom.log2().max(0.0).ceil() as usize + GUARD_BITS;
+        // Round up so small zoom changes don't keep changing the precision
+        bits.div_ceil(32) * 32
+    }
+
+    /// Exact conversion of an `f64` (as long as `frac_bits` covers its exponent).
+    pub fn from_f64(value: f64, frac_bits: usize) -> Self {
+        let (mantissa, exponent, sign) = Float::integer_decode(value);
+        let mut fixed = BigInt::from(mantissa);
+        if sign < 0 {
+            fixed = -fixed;
+        }
+        let shift = exponent as i64 + frac_bits as i64;
+        if shift >= 0 {
+            fixed <<= shift as usize;
+        } else {
+            fixed >>= (-shift) as usize;
+        }
+        Self { mantissa: fixed, frac_bits }
+    }
+
+    /// Parse a decimal number like `-0.7453`, rounded to the nearest value
+    /// with `frac_bits` fractional bits.
+    pub fn parse(text: &str, frac_bits: usize) -> Option<Self> {
+        let text = text.trim();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+        if !int_part.chars().chain(frac_part.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let value: BigInt = format!("{int_part}{frac_part}").parse().ok()?;
+
+        // value / 10^digits in units of 2^-frac_bits, rounded half up
+        let scale = num::traits::pow(BigInt::from(10), frac_part.len());
+        let mut mantissa = ((value << frac_bits) + (&scale >> 1usize)) / scale;
+        if negative {
+            mantissa = -mantissa;
+        }
+        Some(Self { mantissa, frac_bits })
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // Keep only the top bits so the integer conversion can't overflow
+        let shift = self.mantissa.bits().saturating_sub(60);
+        let top = (&self.mantissa >> shift).to_i64().unwrap_or(0) as f64;
+        scale_by_pow2(top, shift as i64 - self.frac_bits as i64)
+    }
+
+    pub fn frac_bits(&self) -> usize {
+        self.frac_bits
+    }
+
+    /// The same value rounded or extended to `frac_bits` fractional bits.
+    pub fn with_precision(&self, frac_bits: usize) -> Self {
+        Self {
+            mantissa: self.aligned(frac_bits),
+            frac_bits,
+        }
+    }
+
+    /// Add an `f64` offset, keeping this number's precision.
+    pub fn add_f64(&self, offset: f64) -> Self {
+        self + &Self::from_f64(offset, self.frac_bits)
+    }
+
+    pub fn square(&self) -> Self {
+        self * self
+    }
+
+    fn aligned(&self, frac_bits: usize) -> BigInt {
+        if frac_bits >= self.frac_bits {
+            &self.mantissa << (frac_bits - self.frac_bits)
+        } else {
+            &self.mantissa >> (self.frac_bits - frac_bits)
+        }
+    }
+}
+
+/// `value * 2^exponent`, split into steps so intermediate powers stay representable.
+fn scale_by_pow2(mut value: f64, mut exponent: i64) -> f64 {
+    while exponent > 1000 {
+        value *= 2f64.powi(1000);
+        exponent -= 1000;
+    }
+    while exponent < -1000 {
+        value *= 2f64.powi(-1000);
+        exponent += 1000;
+    }
+    value * 2f64.powi(exponent as i32)
+}
+
+impl Add for &BigFixed {
+    type Output = BigFixed;
+
+    fn add(self, other: &BigFixed) -> BigFixed {
+        let frac_bits = self.frac_bits.max(other.frac_bits);
+        BigFixed {
+            mantissa: self.aligned(frac_bits) + other.aligned(frac_bits),
+            frac_bits,
+        }
+    }
+}
+
+impl Sub for &BigFixed {
+    type Output = BigFixed;
+
+    fn sub(self, other: &BigFixed) -> BigFixed {
+        let frac_bits = self.frac_bits.max(other.frac_bits);
+        BigFixed {
+            mantissa: self.aligned(frac_bits) - other.aligned(frac_bits),
+            frac_bits,
+        }
+    }
+}
+
+impl Mul for &BigFixed {
+    type Output = BigFixed;
+
+    fn mul(self, other: &BigFixed) -> BigFixed {
+        let frac_bits = self.frac_bits.max(other.frac_bits);
+        let product = &self.mantissa * &other.mantissa;
+        BigFixed {
+            mantissa: product >> (self.frac_bits + other.frac_bits - frac_bits),
+            frac_bits,
+        }
+    }
+}
+
+impl fmt::Display for BigFixed {
+    /// Decimal representation, `{:.N}` limits the number of fractional digits,
+    /// by default all digits the precision can resolve are printed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = f
+            .precision()
+            .unwrap_or((self.frac_bits as f64 * std::f64::consts::LOG10_2).ceil() as usize);
+
+        // Round half up to the requested number of decimal digits
+        let mut scaled = self.mantissa.abs() * num::traits::pow(BigInt::from(10), digits);
+        if self.frac_bits > 0 {
+            scaled = (scaled + (BigInt::from(1) << (self.frac_bits - 1))) >> self.frac_bits;
+        }
+
+        let sign = if self.mantissa.is_negative() && !scaled.is_zero() { "-" } else { "" };
+        let text = format!("{:0>width$}", scaled.to_string(), width = digits + 1);
+        let (int_part, frac_part) = text.split_at(text.len() - digits);
+        if digits == 0 {
+            write!(f, "{sign}{int_part}")
+        } else {
+            write!(f, "{sign}{int_part}.{frac_part}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_display_round_trip() {
+        for frac_bits in [64, 128, 256, 1024] {
+            for text in ["0", "3", "-0.7453", "0.25", "-1.74970000000001234567890123", "0.0000099999999999999999999999999"] {
+                let value = BigFixed::parse(text, frac_bits).unwrap();
+                assert_eq!(BigFixed::parse(&value.to_string(), frac_bits), Some(value), "{text} at {frac_bits} bits");
+            }
+        }
+        let value = BigFixed::parse("-0.7453", 64).unwrap();
+        assert_eq!(format!("{value:.4}"), "-0.7453");
+        assert_eq!(BigFixed::parse("0.5x", 64), None);
+    }
+
+    #[test]
+    fn to_f64_of_negative_and_large_mantissas() {
+        assert_eq!(BigFixed::parse("-0.7453", 128).unwrap().to_f64(), -0.7453);
+        // Far more mantissa bits than an f64 holds
+        let long = BigFixed::parse("-1.74970000000001234567890123", 1024).unwrap();
+        assert!((long.to_f64() + 1.749_700_000_000_012_3).abs() <= f64::EPSILON * 2.0);
+        assert_eq!(BigFixed::from_f64(1e300, 64).to_f64(), 1e300);
+        assert_eq!(BigFixed::from_f64(-3e-200, 800).to_f64(), -3e-200);
+    }
+}
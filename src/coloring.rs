@@ -0,0 +1,332 @@
+use eframe::egui;
+use rayon::prelude::*;
+
+use crate::fractal::Sample;
+use crate::orbit_trap::OrbitTrap;
+use crate::palette::Palette;
+
+/// How escaped points are turned into a color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColoringMode {
+    /// Integer iteration count, shows the classic bands.
+    Iteration,
+    /// Normalized iteration count, continuous across band edges.
+    Smooth,
+    /// Smooth iteration count mapped through the frame's cumulative
+    /// iteration histogram, every color gets used about equally often.
+    Histogram,
+    /// Distance estimate to the boundary, draws the filaments as crisp lines
+    /// of a fixed width in pixels whatever their iteration count.
+    Boundary,
+    /// Closest approach of the orbit to the orbit trap.
+    TrapDistance,
+    /// Iteration at which the orbit first hit the orbit trap.
+    TrapHit,
+}
+
+impl ColoringMode {
+    pub const ALL: [ColoringMode; 6] = [
+        ColoringMode::Iteration,
+        ColoringMode::Smooth,
+        ColoringMode::Histogram,
+        ColoringMode::Boundary,
+        ColoringMode::TrapDistance,
+        ColoringMode::TrapHit,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColoringMode::Iteration => "Iteration count",
+            ColoringMode::Smooth => "Smooth",
+            ColoringMode::Histogram => "Histogram",
+            ColoringMode::Boundary => "Boundary",
+            ColoringMode::TrapDistance => "Orbit trap distance",
+            ColoringMode::TrapHit => "Orbit trap hit",
+        }
+    }
+
+    /// Whether the render has to track the derivative for this mode.
+    pub fn uses_distance(&self) -> bool {
+        *self == ColoringMode::Boundary
+    }
+
+    /// Whether the render has to check the orbits against the orbit trap for this mode.
+    pub fn uses_trap(&self) -> bool {
+        matches!(self, ColoringMode::TrapDistance | ColoringMode::TrapHit)
+    }
+}
+
+/// How points inside the set, which never escape, are colored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InteriorMode {
+    Black,
+    /// |z| after the last iteration.
+    Magnitude,
+    /// Period of the attracting cycle the orbit settles into.
+    Period,
+    /// Iteration at which the orbit came closest to 0.
+    AtomDomain,
+    /// Distance to the boundary of the point's component.
+    Distance,
+}
+
+impl InteriorMode {
+    pub const ALL: [InteriorMode; 5] = [
+        InteriorMode::Black,
+        InteriorMode::Magnitude,
+        InteriorMode::Period,
+        InteriorMode::AtomDomain,
+        InteriorMode::Distance,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InteriorMode::Black => "Black",
+            InteriorMode::Magnitude => "Final |z|",
+            InteriorMode::Period => "Period",
+            InteriorMode::AtomDomain => "Atom domain",
+            InteriorMode::Distance => "Interior distance",
+        }
+    }
+
+    /// Whether the render has to analyze the points inside the set for this mode.
+    pub fn needs_analysis(&self) -> bool {
+        matches!(self, InteriorMode::Period | InteriorMode::AtomDomain | InteriorMode::Distance)
+    }
+}
+
+/// Everything that decides how a rendered frame is colored.
+///
+/// None of it affects the iteration, changing any of it only recolors the
+/// samples already rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorSettings {
+    pub mode: ColoringMode,
+    pub palette: Palette,
+    /// Exponent applied to the normalized value before the palette lookup,
+    /// below 1 spreads out the low iteration counts most points escape with.
+    /// Not used in histogram mode, which spreads the colors by itself.
+    pub gamma: f64,
+    /// Width of the boundary lines in pixels.
+    pub line_thickness: f64,
+    pub lighting: Lighting,
+    pub interior: InteriorMode,
+}
+
+impl ColorSettings {
+    /// Whether the render has to track the derivative for these settings.
+    pub fn needs_derivative(&self) -> bool {
+        self.mode.uses_distance() || self.lighting.enabled
+    }
+}
+
+/// Shading as if the fractal were an embossed surface lit from one direction.
+///
+/// The surface normal of a pixel points along z / z', the direction in which
+/// the potential grows fastest, tilted up out of the image by `height`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lighting {
+    pub enabled: bool,
+    /// Direction the light comes from, degrees counter-clockwise from the right.
+    pub azimuth: f64,
+    /// Angle of the light above the image plane, in degrees.
+    pub elevation: f64,
+    /// Larger values flatten the relief.
+    pub height: f64,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self { enabled: false, azimuth: 45.0, elevation: 45.0, height: 1.0 }
+    }
+}
+
+impl Lighting {
+    /// Brightness of a sample's surface, 0..=1.
+    fn shade(&self, sample: &Sample) -> f32 {
+        let [wx, wy] = sample.z_over_dz;
+        let len = wx.hypot(wy);
+        let (nx, ny) = if len > 0.0 { (wx / len, wy / len) } else { (0.0, 0.0) };
+        let nz = self.height as f32;
+        let norm = (nx * nx + ny * ny + nz * nz).sqrt();
+
+        // Image y points down, flip it so the azimuth turns counter-clockwise on screen
+        let (azimuth, elevation) = (self.azimuth.to_radians() as f32, self.elevation.to_radians() as f32);
+        let light = (elevation.cos() * azimuth.cos(), -elevation.cos() * azimuth.sin(), elevation.sin());
+        ((nx * light.0 + ny * light.1 + nz * light.2) / norm).max(0.0)
+    }
+}
+
+/// Raw per-pixel iteration results of a render, kept so the frame can be
+/// recolored without iterating it again.
+#[derive(Debug, Clone)]
+pub struct SampleBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub samples: Vec<Sample>,
+    /// Extra samples within the pixels, when the frame was supersampled.
+    pub subsamples: Option<Subsamples>,
+    /// Iteration limit the samples were computed with.
+    pub max_iter: i32,
+    /// Whether the samples carry a distance estimate.
+    pub distance_estimated: bool,
+    /// Trap the orbits were checked against, if any.
+    pub orbit_trap: Option<OrbitTrap>,
+    /// Whether the samples inside the set carry period, atom domain and interior distance.
+    pub interior_analyzed: bool,
+    /// Whether every sample inside the set was iterated, none filled in from
+    /// its neighbours, so interior modes other than black can color them.
+    pub interior_individual: bool,
+    /// Supersampled colors and the settings they were colored with, for frames
+    /// that had more subsamples than they could keep.
+    pub colored: Option<(ColorSettings, egui::ColorImage)>,
+}
+
+impl SampleBuffer {
+    /// Whether the samples carry everything `settings` need to be colored.
+    pub fn supports(&self, settings: &ColorSettings) -> bool {
+        (self.distance_estimated || !settings.needs_derivative())
+            && (self.orbit_trap.is_some() || !settings.mode.uses_trap())
+            && (self.interior_analyzed || !settings.interior.needs_analysis())
+            && (self.interior_individual || settings.interior == InteriorMode::Black)
+            && self.colored.as_ref().is_none_or(|(colored, _)| colored == settings)
+    }
+
+    /// Color every sample, cheap compared to iterating them in the first place.
+    ///
+    /// Supersampled pixels get the average color of their subsamples, taken in
+    /// linear space. Averaging the sRGB values would darken the edges.
+    pub fn colorize(&self, settings: &ColorSettings) -> egui::ColorImage {
+        self.colorize_as_part_of(settings, self)
+    }
+
+    /// Like `colorize`, but for one band of a larger frame. Histogram coloring
+    /// takes the distribution of `whole`, any rendering of the full frame, so
+    /// all bands are colored alike.
+    pub fn colorize_as_part_of(&self, settings: &ColorSettings, whole: &SampleBuffer) -> egui::ColorImage {
+        if let Some((colored, image)) = &self.colored
+            && colored == settings
+        {
+            return image.clone();
+        }
+        let histogram = (settings.mode == ColoringMode::Histogram).then(|| Histogram::of(&whole.samples, whole.max_iter));
+        let color = |sample| self.sample_color(sample, settings, histogram.as_ref());
+        let pixels = self
+            .samples
+            .par_iter()
+            .enumerate()
+            .map(|(i, &sample)| match self.subsamples.as_ref().map(|subsamples| subsamples.of(i)) {
+                Some(subsamples) if !subsamples.is_empty() => {
+                    let sum = subsamples.iter().fold(egui::Rgba::TRANSPARENT, |sum, &sample| sum + color(sample).into());
+                    (sum * (1.0 / subsamples.len() as f32)).into()
+                }
+                _ => color(sample),
+            })
+            .collect();
+        egui::ColorImage { size: [self.width, self.height], pixels }
+    }
+
+    /// Map an iterated point to a color of the palette.
+    fn sample_color(&self, sample: Sample, settings: &ColorSettings, histogram: Option<&Histogram>) -> egui::Color32 {
+        if sample.iter == self.max_iter {
+            return Self::interior_color(sample, settings);
+        }
+        let t = match settings.mode {
+            ColoringMode::Iteration => (sample.iter as f64 / self.max_iter as f64).min(1.0).powf(settings.gamma),
+            ColoringMode::Smooth => {
+                let value = sample.smooth_iteration().max(0.0);
+                (value / self.max_iter as f64).min(1.0).powf(settings.gamma)
+            }
+            ColoringMode::Histogram => histogram.map_or(0.0, |histogram| histogram.rank(sample.smooth_iteration())),
+            // Fade from the start of the palette on the boundary to its end a line width away
+            ColoringMode::Boundary => (sample.distance() / settings.line_thickness).min(1.0),
+            ColoringMode::TrapDistance => {
+                let size = self.orbit_trap.map_or(1.0, |trap| trap.size);
+                (sample.trap_distance as f64 / size).min(1.0).powf(settings.gamma)
+            }
+            // Orbits that never hit the trap get the end of the palette
+            ColoringMode::TrapHit => sample
+                .trap_hit
+                .map_or(1.0, |hit| (hit as f64 / self.max_iter as f64).min(1.0).powf(settings.gamma)),
+        };
+        let color = settings.palette.color_at(t as f32);
+        if !settings.lighting.enabled {
+            return color;
+        }
+        let shade = settings.lighting.shade(&sample);
+        let lit = |channel: u8| (channel as f32 * shade).round() as u8;
+        egui::Color32::from_rgb(lit(color.r()), lit(color.g()), lit(color.b()))
+    }
+
+    fn interior_color(sample: Sample, settings: &ColorSettings) -> egui::Color32 {
+        // Spread small integers over the palette by the golden ratio so neighbouring
+        // periods and domains get clearly different colors
+        let spread = |n: i32| (n as f64 * 0.618_034).fract();
+        let t = match settings.interior {
+            InteriorMode::Black => return egui::Color32::BLACK,
+            InteriorMode::Magnitude => (sample.mag_sq.sqrt() / 2.0).min(1.0),
+            InteriorMode::Period if sample.period > 0 => spread(sample.period),
+            InteriorMode::AtomDomain => spread(sample.atom_domain),
+            // Log scale, the distance ranges from a fraction of a pixel to the whole image
+            InteriorMode::Distance if sample.interior_distance > 0.0 => {
+                ((sample.interior_distance as f64).ln_1p() / 8.0).min(1.0)
+            }
+            // The cycle could not be found, the point is too close to the boundary
+            InteriorMode::Period | InteriorMode::Distance => return egui::Color32::BLACK,
+        };
+        settings.palette.color_at(t as f32)
+    }
+}
+
+/// Samples spread over the area of some or all pixels of a frame.
+#[derive(Debug, Clone)]
+pub struct Subsamples {
+    /// The subsamples of pixel `i` are `samples[offsets[i]..offsets[i + 1]]`.
+    pub offsets: Vec<usize>,
+    pub samples: Vec<Sample>,
+}
+
+impl Subsamples {
+    /// Subsamples of pixel `i`, empty if it has none.
+    fn of(&self, i: usize) -> &[Sample] {
+        &self.samples[self.offsets[i]..self.offsets[i + 1]]
+    }
+}
+
+/// Cumulative distribution of the iteration counts of the escaped pixels of a frame.
+struct Histogram {
+    /// `cdf[n]` is the fraction of escaped pixels with at most `n` iterations.
+    cdf: Vec<f64>,
+}
+
+impl Histogram {
+    fn of(samples: &[Sample], max_iter: i32) -> Self {
+        // Sized by the counts that occur, the limit itself may be huge. One
+        // bucket past the highest for smooth values to rise towards.
+        let escaped = || samples.iter().filter(|sample| sample.iter < max_iter).map(|sample| sample.iter.max(0) as usize);
+        let mut counts = vec![0u64; escaped().max().unwrap_or(0) + 2];
+        for iter in escaped() {
+            counts[iter] += 1;
+        }
+        let total = counts.iter().sum::<u64>().max(1) as f64;
+        let mut running = 0;
+        let cdf = counts
+            .iter()
+            .map(|&count| {
+                running += count;
+                running as f64 / total
+            })
+            .collect();
+        Self { cdf }
+    }
+
+    /// Position of a (smooth) iteration count in the distribution, 0..=1.
+    ///
+    /// Interpolates between neighbouring counts so smooth values stay smooth.
+    fn rank(&self, value: f64) -> f64 {
+        let value = value.clamp(0.0, self.cdf.len() as f64 - 1.0);
+        let n = value.floor() as usize;
+        let below = if n > 0 { self.cdf[n - 1] } else { 0.0 };
+        below + (self.cdf[n] - below) * value.fract()
+    }
+}
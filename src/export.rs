@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use eframe::egui;
+use image::ColorType;
+
+use crate::coloring::{ColorSettings, SampleBuffer};
+use crate::fractal::{CancelToken, FractalType, FractalView, RenderedFrame};
+use crate::location::Location;
+
+/// Samples, subsamples included, a band of the exported image holds at most.
+/// Keeps the memory of large exports bounded.
+const BAND_SAMPLES: usize = 1 << 22;
+
+const JPEG_QUALITY: u8 = 95;
+
+/// Image export running on a thread of its own, so the explorer stays usable.
+///
+/// The view is rendered in horizontal bands that are colored as soon as they
+/// are done, only the final RGB image is held in full.
+pub struct Export {
+    pub path: PathBuf,
+    height: usize,
+    rows_done: Arc<AtomicUsize>,
+    cancel: Arc<AtomicU64>,
+    result: Receiver<Result<(), String>>,
+}
+
+impl Export {
+    /// Render `view` at its size and save it to `path`, as PNG or JPEG going by
+    /// the extension.
+    ///
+    /// `screen` is the frame on screen, if it shows the same view. Histogram
+    /// coloring takes its distribution from it, so the export looks the same.
+    pub fn start(
+        ctx: egui::Context,
+        view: FractalView,
+        colors: ColorSettings,
+        screen: Option<Arc<SampleBuffer>>,
+        path: PathBuf,
+        threads: usize,
+    ) -> Self {
+        let (result_tx, result_rx) = mpsc::channel();
+        let rows_done = Arc::new(AtomicUsize::new(0));
+        let cancel = Arc::new(AtomicU64::new(0));
+
+        let export = Self { path: path.clone(), height: view.height, rows_done: rows_done.clone(), cancel: cancel.clone(), result: result_rx };
+        thread::Builder::new()
+            .name("fractal-export".to_owned())
+            .spawn(move || {
+                let cancel = CancelToken::new(cancel, 0);
+                let result = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads.max(1))
+                    .thread_name(|i| format!("fractal-export-{i}"))
+                    .build()
+                    .map_err(|err| err.to_string())
+                    .and_then(|pool| {
+                        let on_band = || ctx.request_repaint();
+                        let rendered = pool.install(|| render(&view, &colors, screen.as_deref(), &cancel, &rows_done, on_band));
+                        let (rgb, max_iter) = rendered.ok_or_else(|| "Export cancelled".to_owned())?;
+                        save(&path, &rgb, view.width, view.height, &Location::of(&view, max_iter, &colors.palette))
+                    });
+                let _ = result_tx.send(result);
+                ctx.request_repaint();
+            })
+            .expect("failed to spawn export thread");
+        export
+    }
+
+    /// Fraction of the image rendered so far.
+    pub fn progress(&self) -> f32 {
+        self.rows_done.load(Ordering::Relaxed) as f32 / self.height.max(1) as f32
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The outcome, once the export has finished.
+    pub fn finished(&self) -> Option<Result<(), String>> {
+        self.result.try_recv().ok()
+    }
+}
+
+/// Render and color the view band by band into one RGB buffer, `None` if
+/// cancelled. Also returns the iteration limit the bands were rendered with.
+fn render(
+    view: &FractalView,
+    colors: &ColorSettings,
+    screen: Option<&SampleBuffer>,
+    cancel: &CancelToken,
+    rows_done: &AtomicUsize,
+    on_band: impl Fn(),
+) -> Option<(Vec<u8>, i32)> {
+    // The Koch curve is laid out around the view center, it can't be split up.
+    // Nor does it iterate anything.
+    let grid = view.supersampling.grid();
+    let (band_rows, max_iter) = match view.fractal_type {
+        FractalType::Koch => (view.height, 0),
+        _ => ((BAND_SAMPLES / (view.width * grid * grid)).clamp(1, view.height), view.max_iterations(cancel)?),
+    };
+
+    let mut rgb = Vec::with_capacity(view.width * view.height * 3);
+    for top in (0..view.height).step_by(band_rows) {
+        let rows = band_rows.min(view.height - top);
+        let (frame, _) = view.band(top, rows, max_iter).generate_fractal_image(cancel, |_| {})?;
+        let image = match frame {
+            RenderedFrame::Image(image) => image,
+            RenderedFrame::Samples(samples) => samples.colorize_as_part_of(colors, screen.unwrap_or(&samples)),
+        };
+        rgb.extend(image.pixels.iter().flat_map(|color| [color.r(), color.g(), color.b()]));
+        rows_done.store(top + rows, Ordering::Relaxed);
+        on_band();
+    }
+    Some((rgb, max_iter))
+}
+
+/// Write the image, PNGs get the location in their text chunks.
+fn save(path: &Path, rgb: &[u8], width: usize, height: usize, location: &Location) -> Result<(), String> {
+    let (width, height) = (width as u32, height as u32);
+    let format = image::ImageFormat::from_path(path).ok();
+    if !matches!(format, Some(image::ImageFormat::Png | image::ImageFormat::Jpeg)) {
+        return Err("Save as .png, .jpg or .jpeg".to_owned());
+    }
+    let mut file = BufWriter::new(File::create(path).map_err(|err| err.to_string())?);
+    if format == Some(image::ImageFormat::Jpeg) {
+        return image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, JPEG_QUALITY)
+            .encode(rgb, width, height, ColorType::Rgb8)
+            .map_err(|err| err.to_string());
+    }
+
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in location.to_text() {
+        encoder.add_text_chunk(keyword.to_owned(), text).map_err(|err| err.to_string())?;
+    }
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    writer.write_image_data(rgb).map_err(|err| err.to_string())
+}
@@ -0,0 +1,1037 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+use num::complex::Complex64;
+use rayon::prelude::*;
+
+use crate::bigfixed::BigFixed;
+use crate::coloring::{ColorSettings, ColoringMode, InteriorMode, Lighting, SampleBuffer, Subsamples};
+use crate::palette::Palette;
+use crate::interior;
+use crate::orbit_trap::{OrbitTrap, TrapState};
+use crate::perturbation::ReferenceOrbit;
+use crate::subdivision;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FractalType {
+    Mandelbrot,
+    Julia,
+    Koch,
+}
+
+impl FractalType {
+    pub const ALL: [FractalType; 3] = [FractalType::Mandelbrot, FractalType::Julia, FractalType::Koch];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FractalType::Mandelbrot => "Mandelbrot Set",
+            FractalType::Julia => "Julia Set",
+            FractalType::Koch => "Koch Curve",
+        }
+    }
+
+    /// Colors the fractal starts out with.
+    pub fn default_colors(&self) -> ColorSettings {
+        let (palette, gamma) = match self {
+            FractalType::Julia => (Palette::rainbow(), 0.7),
+            _ => (Palette::hot(), 0.5),
+        };
+        ColorSettings {
+            mode: ColoringMode::Smooth,
+            palette,
+            gamma,
+            line_thickness: 1.0,
+            lighting: Lighting::default(),
+            interior: InteriorMode::Black,
+        }
+    }
+}
+
+/// Result of the escape-time iteration of a single point.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sample {
+    pub iter: i32,
+    /// |z|² after the last iteration.
+    pub mag_sq: f64,
+    /// z / z' in pixels, z' being dz/dc (Mandelbrot) or dz/dz0 (Julia).
+    /// Zero when the derivative was not tracked.
+    pub z_over_dz: [f32; 2],
+    /// Closest approach of the orbit to the orbit trap.
+    pub trap_distance: f32,
+    /// First iteration that hit the orbit trap.
+    pub trap_hit: Option<i32>,
+    /// Iteration at which |z| was smallest, the atom domain the point lies in.
+    pub atom_domain: i32,
+    /// Period of the attracting cycle of a point inside the set, 0 if unknown.
+    pub period: i32,
+    /// Interior distance estimate in pixels, 0 if unknown.
+    pub interior_distance: f32,
+    /// Recognized as inside the set without running out of iterations.
+    pub short_circuited: bool,
+}
+
+/// Optional quantities the escape loop carries along an orbit besides z itself.
+#[derive(Debug, Clone, Copy)]
+pub struct OrbitTracking {
+    fractal_type: FractalType,
+    /// Distance estimates are expressed in pixels of this size.
+    pixel_size: f64,
+    /// Derivative dz/dc (Mandelbrot) or dz/dz0 (Julia), when tracked.
+    dz: Option<(f64, f64)>,
+    /// What the derivative gains every step: 1 for dz/dc, 0 for dz/dz0.
+    dz_step: f64,
+    trap: Option<(OrbitTrap, TrapState)>,
+    /// Smallest |z|² so far and the iteration it was reached at, when
+    /// points inside the set are analyzed.
+    atom: Option<(f64, i32)>,
+}
+
+impl OrbitTracking {
+    /// Tracking from the start of an orbit, of the derivative when `derivative`
+    /// is set, the trap when there is one and of what's needed to analyze
+    /// points inside the set when `interior` is set.
+    pub fn new(
+        fractal_type: FractalType,
+        pixel_size: f64,
+        derivative: bool,
+        trap: Option<OrbitTrap>,
+        interior: bool,
+    ) -> Self {
+        let dz_step = if fractal_type == FractalType::Mandelbrot { 1.0 } else { 0.0 };
+        Self {
+            fractal_type,
+            pixel_size,
+            dz: derivative.then_some((1.0 - dz_step, 0.0)),
+            dz_step,
+            trap: trap.map(|trap| (trap, TrapState::default())),
+            atom: interior.then_some((f64::INFINITY, 0)),
+        }
+    }
+
+    /// Whether the whole orbit has to be seen to find the atom domain.
+    pub fn tracks_atom_domain(&self) -> bool {
+        self.atom.is_some()
+    }
+
+    pub fn pixel_size(&self) -> f64 {
+        self.pixel_size
+    }
+
+    /// Replace the tracked derivative, for orbits that skip their first iterations.
+    pub fn set_derivative(&mut self, dz: (f64, f64)) {
+        if self.dz.is_some() {
+            self.dz = Some(dz);
+        }
+    }
+
+    /// Advance the derivative over the iteration starting at `z`.
+    #[inline]
+    pub fn step(&mut self, z: (f64, f64)) {
+        if let Some((dx, dy)) = self.dz {
+            self.dz = Some((2.0 * (z.0 * dx - z.1 * dy) + self.dz_step, 2.0 * (z.0 * dy + z.1 * dx)));
+        }
+    }
+
+    /// Record the orbit point `z` reached after `iter` iterations.
+    #[inline]
+    pub fn visit(&mut self, z: (f64, f64), iter: i32) {
+        if let Some((trap, state)) = &mut self.trap {
+            state.visit(trap, z, iter);
+        }
+        if let Some(atom) = &mut self.atom {
+            let mag_sq = z.0 * z.0 + z.1 * z.1;
+            if mag_sq < atom.0 {
+                *atom = (mag_sq, iter);
+            }
+        }
+    }
+
+    /// Sample of the orbit of `c` that stopped at `z` after `iter` iterations.
+    pub fn finish(&self, iter: i32, z: (f64, f64), c: (f64, f64)) -> Sample {
+        let mag_sq = z.0 * z.0 + z.1 * z.1;
+        let z_over_dz = match self.dz {
+            Some((dx, dy)) if dx != 0.0 || dy != 0.0 => {
+                // z / dz, scaled to pixels so it fits an f32 at any zoom. Divided by
+                // |dz| rather than |dz|², which overflows once |dz| passes 1e154.
+                let norm = dx.hypot(dy);
+                let (ux, uy) = (dx / norm, dy / norm);
+                let scale = 1.0 / (norm * self.pixel_size);
+                [((z.0 * ux + z.1 * uy) * scale) as f32, ((z.1 * ux - z.0 * uy) * scale) as f32]
+            }
+            _ => [0.0; 2],
+        };
+        let trap = self.trap.map(|(_, state)| state).unwrap_or_default();
+
+        // Points that never escaped get their attracting cycle analyzed
+        let interior = self
+            .atom
+            .filter(|_| mag_sq < BAILOUT_SQ)
+            .and_then(|_| interior::analyze(self.fractal_type, z, c));
+        Sample {
+            iter,
+            mag_sq,
+            z_over_dz,
+            trap_distance: trap.min_distance as f32,
+            trap_hit: trap.hit,
+            atom_domain: self.atom.map_or(0, |(_, iter)| iter),
+            period: interior.as_ref().map_or(0, |interior| interior.period),
+            interior_distance: interior
+                .and_then(|interior| interior.distance)
+                .map_or(0.0, |distance| (distance / self.pixel_size) as f32),
+            short_circuited: false,
+        }
+    }
+}
+
+impl Sample {
+    /// Exterior distance estimate `|z| ln |z| / |z'|` to the fractal's boundary, in pixels.
+    pub fn distance(&self) -> f64 {
+        let [wx, wy] = self.z_over_dz;
+        (wx as f64).hypot(wy as f64) * 0.5 * self.mag_sq.ln()
+    }
+
+    /// Normalized iteration count `n + 1 - log2(ln |z|)`, continuous in the pixel position.
+    pub fn smooth_iteration(&self) -> f64 {
+        let log_mag = 0.5 * self.mag_sq.ln();
+        self.iter as f64 + 1.0 - log_mag.ln().log2()
+    }
+}
+
+type Segment = ((f64, f64), (f64, f64));
+
+/// Squared escape radius. Large enough for the log-log correction of smooth
+/// coloring to be accurate, so every coloring mode can share the same samples.
+pub const BAILOUT_SQ: f64 = 256.0 * 256.0;
+
+/// Distance, in pixels, below which an orbit counts as having returned to an earlier point.
+const PERIODICITY_TOLERANCE: f64 = 1e-3;
+
+/// Bits of f64 precision a pixel has to span along its orbit. Below that
+/// neighbouring pixels start to blur together and the iteration has to switch
+/// to perturbation against a high precision reference.
+const DEEP_ZOOM_PIXEL_BITS: i32 = 40;
+
+/// Deepest zoom a view may have. Pixel offsets from the center are plain f64,
+/// much further they would fall into subnormals and on to zero.
+pub const MAX_ZOOM: f64 = 1e300;
+
+/// How many extra reference orbits a pass may spend on fixing glitched pixels.
+const MAX_EXTRA_REFERENCES: usize = 16;
+
+/// How often the adaptive iteration limit may double, bounds the time spent
+/// probing views that have boundary everywhere.
+const MAX_ADAPTIVE_DOUBLINGS: usize = 10;
+
+/// Pixel spacing of the grid the adaptive iteration limit is probed on.
+const ADAPTIVE_PROBE_STEP: usize = 16;
+
+/// Difference in smooth iteration count between neighbouring pixels that
+/// makes adaptive supersampling treat them as an edge.
+const EDGE_THRESHOLD: f64 = 1.0;
+
+/// Subsamples a frame holds at most, some 400 MB of them. Past that they are
+/// iterated in bands and only their averaged colors are kept. Exports render
+/// in bands small enough to stay below it.
+const MAX_SUBSAMPLES: usize = 1 << 23;
+
+/// Pixel spacing of the progressive render passes, from the coarse preview down to full resolution.
+const PROGRESSIVE_STEPS: &[usize] = &[8, 4, 2, 1];
+
+/// Fill every `step` x `step` block with its computed top-left sample.
+fn upscale_preview(samples: &[Sample], width: usize, step: usize) -> Vec<Sample> {
+    let height = samples.len() / width;
+    let mut preview = Vec::with_capacity(samples.len());
+    for y in 0..height {
+        let src_row = (y - y % step) * width;
+        for x in 0..width {
+            preview.push(samples[src_row + x - x % step]);
+        }
+    }
+    preview
+}
+
+/// What a render produces. Escape-time fractals hand back their raw samples
+/// so they can be colored, and recolored, separately from iterating them.
+pub enum RenderedFrame {
+    Image(egui::ColorImage),
+    Samples(Arc<SampleBuffer>),
+}
+
+/// Plain f64 escape-time loop, continuing from `z` after `iter` iterations
+/// with whatever `tracking` has gathered up to there.
+///
+/// Orbits caught in an attracting cycle are recognized with Brent's method:
+/// the orbit is compared against a saved point which moves ahead every time
+/// the distance in iterations to it doubles, so cycles of any period are found
+/// after at most a few times their period plus the time to settle.
+pub fn escape_time_f64(
+    z: (f64, f64),
+    c: (f64, f64),
+    mut tracking: OrbitTracking,
+    mut iter: i32,
+    max_iter: i32,
+) -> Sample {
+    // Close enough to the boundary, escaping orbits can pass by earlier points closely
+    let tolerance = tracking.pixel_size() * PERIODICITY_TOLERANCE;
+    let (mut zx, mut zy) = z;
+    let (mut saved, mut saved_at, mut interval) = (z, iter, 1);
+    while zx * zx + zy * zy < BAILOUT_SQ && iter < max_iter {
+        tracking.step((zx, zy));
+        let xtemp = zx * zx - zy * zy + c.0;
+        zy = 2.0 * zx * zy + c.1;
+        zx = xtemp;
+        iter += 1;
+        tracking.visit((zx, zy), iter);
+
+        if (zx - saved.0).abs() + (zy - saved.1).abs() < tolerance {
+            // Back where it was before, the orbit is periodic and will never escape
+            let mut sample = tracking.finish(max_iter, (zx, zy), c);
+            sample.short_circuited = true;
+            return sample;
+        }
+        if iter - saved_at == interval {
+            saved = (zx, zy);
+            saved_at = iter;
+            interval *= 2;
+        }
+    }
+    tracking.finish(iter, (zx, zy), c)
+}
+
+/// The attracting cycle point of `c` if it lies in the main cardioid or the
+/// period-2 bulb of the Mandelbrot set, worked out without iterating.
+fn main_component_cycle(c: (f64, f64)) -> Option<(f64, f64)> {
+    let (x, y) = c;
+    let q = (x - 0.25) * (x - 0.25) + y * y;
+    if q * (q + x - 0.25) <= 0.25 * y * y {
+        // Attracting fixed point z = (1 - sqrt(1 - 4c)) / 2
+        let w = Complex64::new(1.0 - 4.0 * x, -4.0 * y).sqrt();
+        return Some(((1.0 - w.re) / 2.0, -w.im / 2.0));
+    }
+    if (x + 1.0) * (x + 1.0) + y * y <= 1.0 / 16.0 {
+        // Point of the 2-cycle, a root of z² + z + c + 1 = 0
+        let w = Complex64::new(-3.0 - 4.0 * x, -4.0 * y).sqrt();
+        return Some(((w.re - 1.0) / 2.0, w.im / 2.0));
+    }
+    None
+}
+
+/// Order in which the pixels of an escape-time frame are worked through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderStrategy {
+    /// Every pixel, in passes from coarse to fine that are shown as they finish.
+    Progressive,
+    /// Mariani-Silver subdivision, fills rectangles with a uniform border
+    /// without iterating their inside.
+    RectangleSubdivision,
+}
+
+impl RenderStrategy {
+    pub const ALL: [RenderStrategy; 2] = [RenderStrategy::Progressive, RenderStrategy::RectangleSubdivision];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RenderStrategy::Progressive => "Progressive",
+            RenderStrategy::RectangleSubdivision => "Rectangle subdivision",
+        }
+    }
+}
+
+/// Extra samples spread over the area of a pixel to smooth its edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Supersampling {
+    Off,
+    Grid2,
+    Grid3,
+    Grid4,
+    /// A 4x4 grid, but only in pixels that differ strongly from a neighbour.
+    Adaptive,
+}
+
+impl Supersampling {
+    pub const ALL: [Supersampling; 5] = [
+        Supersampling::Off,
+        Supersampling::Grid2,
+        Supersampling::Grid3,
+        Supersampling::Grid4,
+        Supersampling::Adaptive,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Supersampling::Off => "Off",
+            Supersampling::Grid2 => "2x2",
+            Supersampling::Grid3 => "3x3",
+            Supersampling::Grid4 => "4x4",
+            Supersampling::Adaptive => "Adaptive",
+        }
+    }
+
+    /// Samples along each side of a supersampled pixel.
+    pub fn grid(&self) -> usize {
+        match self {
+            Supersampling::Off => 1,
+            Supersampling::Grid2 => 2,
+            Supersampling::Grid3 => 3,
+            Supersampling::Grid4 | Supersampling::Adaptive => 4,
+        }
+    }
+}
+
+/// Start of every pixel's subsamples among `pixels` of them when the `selected`
+/// ones, in order, get `per_pixel` each, with the total at the end.
+fn subsample_offsets(pixels: usize, selected: &[usize], per_pixel: usize) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(pixels + 1);
+    let mut selected = selected.iter().peekable();
+    let mut next = 0;
+    for i in 0..pixels {
+        offsets.push(next);
+        if selected.next_if(|&&j| j == i).is_some() {
+            next += per_pixel;
+        }
+    }
+    offsets.push(next);
+    offsets
+}
+
+/// Whether the pixel at index `i` is on an edge: inside the set while a
+/// neighbour isn't, or the other way round, or more than `EDGE_THRESHOLD`
+/// smooth iterations apart from one. These are the pixels that alias.
+fn is_edge(samples: &[Sample], width: usize, i: usize, max_iter: i32) -> bool {
+    let (x, y) = (i % width, i / width);
+    let height = samples.len() / width;
+    let sample = &samples[i];
+    let neighbours = [
+        (x > 0).then(|| i - 1),
+        (x + 1 < width).then(|| i + 1),
+        (y > 0).then(|| i - width),
+        (y + 1 < height).then(|| i + width),
+    ];
+    neighbours.into_iter().flatten().any(|j| {
+        let other = &samples[j];
+        match (sample.iter == max_iter, other.iter == max_iter) {
+            (true, true) => false,
+            (false, false) => (sample.smooth_iteration() - other.smooth_iteration()).abs() > EDGE_THRESHOLD,
+            _ => true,
+        }
+    })
+}
+
+/// How the iteration limit of a render is chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaxIterMode {
+    /// `base + per_decade * log10(zoom)`.
+    Auto,
+    Fixed,
+    /// Starts from the auto formula and doubles the limit until few enough
+    /// of the boundary pixels run into it.
+    Adaptive,
+}
+
+impl MaxIterMode {
+    pub const ALL: [MaxIterMode; 3] = [MaxIterMode::Auto, MaxIterMode::Fixed, MaxIterMode::Adaptive];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaxIterMode::Auto => "Auto",
+            MaxIterMode::Fixed => "Fixed",
+            MaxIterMode::Adaptive => "Adaptive",
+        }
+    }
+}
+
+/// The iteration limit mode together with the parameters of every mode, so
+/// switching back and forth keeps them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaxIterPolicy {
+    pub mode: MaxIterMode,
+    /// Limit of the auto formula at zoom 1.
+    pub base: f64,
+    /// Iterations the auto formula adds for every tenfold zoom.
+    pub per_decade: f64,
+    pub fixed: i32,
+    /// Percentage of boundary pixels the adaptive limit lets run into it.
+    pub boundary_percent: f64,
+}
+
+impl Default for MaxIterPolicy {
+    fn default() -> Self {
+        Self { mode: MaxIterMode::Auto, base: 255.0, per_decade: 100.0, fixed: 1000, boundary_percent: 1.0 }
+    }
+}
+
+impl MaxIterPolicy {
+    /// Limit the auto formula gives at `zoom`.
+    pub fn auto(&self, zoom: f64) -> i32 {
+        // Increase max iterations for higher zoom levels to maintain detail
+        (self.base + (zoom.log10() * self.per_decade).max(0.0)).max(1.0) as i32
+    }
+}
+
+/// Lets a running render notice that a newer view has been requested
+/// and its result is no longer wanted.
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    latest: Arc<AtomicU64>,
+    generation: u64,
+}
+
+impl CancelToken {
+    pub fn new(latest: Arc<AtomicU64>, generation: u64) -> Self {
+        Self { latest, generation }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.latest.load(Ordering::Relaxed) != self.generation
+    }
+}
+
+/// Snapshot of everything needed to render one frame, detached from the UI state
+/// so it can be handed over to the render worker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FractalView {
+    /// Size of the rendered frame in pixels.
+    pub width: usize,
+    pub height: usize,
+    pub center_x: BigFixed,
+    pub center_y: BigFixed,
+    pub zoom: f64,
+    pub fractal_type: FractalType,
+    pub julia_c_real: f64,
+    pub julia_c_imag: f64,
+    pub series_approximation: bool,
+    /// Track the derivative for the distance estimate, costs some speed.
+    pub distance_estimation: bool,
+    pub orbit_trap: Option<OrbitTrap>,
+    /// Work out cycle period, atom domain and interior distance of points inside the set.
+    pub interior_analysis: bool,
+    /// Whether points inside the set are colored individually rather than
+    /// all alike, so they can't be filled in from their neighbours.
+    pub interior_coloring: bool,
+    pub strategy: RenderStrategy,
+    /// Only let rectangle subdivision fill rectangles inside the set, after
+    /// checking their center too.
+    pub fill_guard: bool,
+    pub max_iter_policy: MaxIterPolicy,
+    pub supersampling: Supersampling,
+    /// Coloring for supersampled frames with more subsamples than they can keep.
+    pub colors: ColorSettings,
+}
+
+/// Figures about a finished render worth showing to the user.
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    /// Iterations skipped per pixel by the series approximation.
+    pub series_skipped: usize,
+    /// Pixels recognized as inside the set without running out of iterations.
+    pub short_circuited: usize,
+    /// Pixels filled in by rectangle subdivision without being iterated.
+    pub filled: usize,
+    pub pixels: usize,
+    /// Pixels that got extra samples.
+    pub supersampled: usize,
+    /// Iteration limit the render ended up with.
+    pub max_iter: i32,
+}
+
+impl FractalView {
+    /// Render the view, returns `None` when the render was cancelled before it finished.
+    ///
+    /// Escape-time fractals are rendered progressively by default: every coarse pass
+    /// is handed to `on_preview` upscaled to full size before the next, finer pass
+    /// starts. Rectangle subdivision has no previews. Supersampling comes last,
+    /// the frame without it is handed to `on_preview` first.
+    pub fn generate_fractal_image(
+        &self,
+        cancel: &CancelToken,
+        mut on_preview: impl FnMut(RenderedFrame),
+    ) -> Option<(RenderedFrame, RenderStats)> {
+        let mut stats = RenderStats::default();
+
+        if self.fractal_type == FractalType::Koch {
+            let mut image = egui::ColorImage::new([self.width, self.height], egui::Color32::BLACK);
+            self.generate_koch_curve(&mut image);
+            return Some((RenderedFrame::Image(image), stats));
+        }
+
+        let pixel_offset = |x: usize, y: usize| self.pixel_offset(x as f64, y as f64);
+
+        // Beyond f64 resolution pixels are iterated as deltas from a reference orbit,
+        // the adaptive limit may already have computed it
+        let (max_iter, reference) = self.iteration_limit(cancel)?;
+        stats.max_iter = max_iter;
+        let tracking = OrbitTracking::new(
+            self.fractal_type,
+            self.pixel_size(),
+            self.distance_estimation,
+            self.orbit_trap,
+            self.interior_analysis,
+        );
+        let reference = match reference {
+            Some(reference) => Some(reference),
+            None if self.is_deep_zoom() => Some(self.reference_orbit((0.0, 0.0), max_iter, cancel)?),
+            None => None,
+        };
+
+        // Probe the view's corners and edge midpoints to see how far the series holds.
+        // Traps and atom domains need to see every orbit point, so they can't skip any.
+        let use_series = self.series_approximation && self.orbit_trap.is_none() && !self.interior_analysis;
+        let series = reference.as_ref().filter(|_| use_series).map(|reference| {
+            let (w, h) = (self.width - 1, self.height - 1);
+            let probes: Vec<_> = [(0, 0), (w / 2, 0), (w, 0), (0, h / 2), (w, h / 2), (0, h), (w / 2, h), (w, h)]
+                .into_iter()
+                .map(|(x, y)| pixel_offset(x, y))
+                .collect();
+            reference.series_approximation(&probes, max_iter)
+        });
+        stats.series_skipped = series.as_ref().map_or(0, |series| series.skip);
+        let center_x = self.center_x.to_f64();
+        let center_y = self.center_y.to_f64();
+
+        let mut samples = vec![Sample::default(); self.width * self.height];
+
+        // Iterate the point at an offset from the view center, also telling
+        // whether its perturbation glitched
+        let iterate = |(dx, dy): (f64, f64)| match &reference {
+            Some(reference) => {
+                let pixel = reference.iterate((dx, dy), max_iter, series.as_ref(), tracking);
+                (pixel.sample, pixel.glitched)
+            }
+            None => (self.escape_time(center_x + dx, center_y + dy, tracking, max_iter), false),
+        };
+        let compute = |x: usize, y: usize| iterate(pixel_offset(x, y));
+
+        // Redo glitched samples, given by their index and offset, against a new
+        // reference placed on one of them. Every round fixes at least the sample
+        // the reference sits on.
+        let fix_glitches = |samples: &mut [Sample], mut glitched: Vec<(usize, (f64, f64))>| {
+            for _ in 0..MAX_EXTRA_REFERENCES {
+                let Some(&(_, offset)) = glitched.get(glitched.len() / 2) else {
+                    break;
+                };
+                let Some(reference) = self.reference_orbit(offset, max_iter, cancel) else {
+                    return false;
+                };
+                let results: Vec<_> = glitched
+                    .par_iter()
+                    .map(|&(_, offset)| reference.iterate(offset, max_iter, None, tracking))
+                    .collect();
+                let mut still_glitched = Vec::new();
+                for (&(i, offset), pixel) in glitched.iter().zip(results) {
+                    samples[i] = pixel.sample;
+                    if pixel.glitched {
+                        still_glitched.push((i, offset));
+                    }
+                }
+                glitched = still_glitched;
+                if cancel.is_cancelled() {
+                    return false;
+                }
+            }
+            true
+        };
+
+        if self.strategy == RenderStrategy::RectangleSubdivision {
+            // Distance estimates and traps vary across escaped bands, unlike the iteration count
+            let fill = subdivision::Fill {
+                inside: !self.interior_coloring,
+                outside: !self.fill_guard && !self.distance_estimation && self.orbit_trap.is_none(),
+                check_center: self.fill_guard,
+            };
+            let subdivided = subdivision::render(&mut samples, self.width, max_iter, fill, cancel, compute)?;
+            let glitched = subdivided.glitched.iter().map(|&(x, y)| (y * self.width + x, pixel_offset(x, y)));
+            if !fix_glitches(&mut samples, glitched.collect()) {
+                return None;
+            }
+            stats.filled = subdivided.filled;
+        } else {
+            // Each pass only computes the pixels on its grid that the previous,
+            // coarser pass did not already cover
+            let mut previous_step = None;
+            for &step in PROGRESSIVE_STEPS {
+                let glitched = Mutex::new(Vec::new());
+                // Rows are independent, spread them over the worker's thread pool
+                samples.par_chunks_mut(self.width).enumerate().for_each(|(y, row)| {
+                    // A newer view supersedes this one, don't bother finishing it
+                    if y % step != 0 || cancel.is_cancelled() {
+                        return;
+                    }
+                    for x in (0..self.width).step_by(step) {
+                        if previous_step.is_some_and(|prev| x % prev == 0 && y % prev == 0) {
+                            continue;
+                        }
+                        let (sample, pixel_glitched) = compute(x, y);
+                        if pixel_glitched {
+                            glitched.lock().unwrap().push((y * self.width + x, pixel_offset(x, y)));
+                        }
+                        row[x] = sample;
+                    }
+                });
+                if cancel.is_cancelled() || !fix_glitches(&mut samples, glitched.into_inner().unwrap()) {
+                    return None;
+                }
+                if step > 1 {
+                    on_preview(self.sample_frame(upscale_preview(&samples, self.width, step), max_iter, None));
+                }
+                previous_step = Some(step);
+            }
+        }
+        stats.short_circuited = samples.iter().filter(|sample| sample.short_circuited).count();
+        stats.pixels = samples.len();
+
+        // Spread a grid of extra samples over the area of every pixel, or only of
+        // those on edges, the coloring averages them
+        let mut frame = self.sample_buffer(samples, max_iter, None);
+        if let Some(selected) = self.supersampled_pixels(&frame.samples, max_iter) {
+            // Show the frame as it is while the extra samples are computed
+            on_preview(RenderedFrame::Samples(Arc::new(frame.clone())));
+
+            let grid = self.supersampling.grid();
+            let per_pixel = grid * grid;
+            // Subsamples of the given pixels, in order, `None` if cancelled
+            let subsample = |pixels: &[usize]| {
+                let glitched = Mutex::new(Vec::new());
+                let mut subsamples: Vec<Sample> = (0..pixels.len() * per_pixel)
+                    .into_par_iter()
+                    .map(|k| {
+                        if cancel.is_cancelled() {
+                            return Sample::default();
+                        }
+                        let (i, sub) = (pixels[k / per_pixel], k % per_pixel);
+                        let x = (i % self.width) as f64 + ((sub % grid) as f64 + 0.5) / grid as f64 - 0.5;
+                        let y = (i / self.width) as f64 + ((sub / grid) as f64 + 0.5) / grid as f64 - 0.5;
+                        let offset = self.pixel_offset(x, y);
+                        let (sample, sample_glitched) = iterate(offset);
+                        if sample_glitched {
+                            glitched.lock().unwrap().push((k, offset));
+                        }
+                        sample
+                    })
+                    .collect();
+                let fixed = !cancel.is_cancelled() && fix_glitches(&mut subsamples, glitched.into_inner().unwrap());
+                fixed.then_some(subsamples)
+            };
+
+            if selected.len() * per_pixel <= MAX_SUBSAMPLES {
+                frame.subsamples = Some(Subsamples {
+                    offsets: subsample_offsets(frame.samples.len(), &selected, per_pixel),
+                    samples: subsample(&selected)?,
+                });
+            } else {
+                // Too many to keep: iterate them a band of rows at a time and
+                // keep only the colors they average to
+                let rows = (MAX_SUBSAMPLES / (self.width * per_pixel)).max(1);
+                let mut pixels = Vec::with_capacity(frame.samples.len());
+                let mut remaining = &selected[..];
+                for top in (0..self.height).step_by(rows) {
+                    let rows = rows.min(self.height - top);
+                    let (start, end) = (top * self.width, (top + rows) * self.width);
+                    let (band_selected, rest) = remaining.split_at(remaining.partition_point(|&i| i < end));
+                    remaining = rest;
+                    let relative: Vec<usize> = band_selected.iter().map(|i| i - start).collect();
+                    let band = SampleBuffer {
+                        height: rows,
+                        samples: frame.samples[start..end].to_vec(),
+                        subsamples: Some(Subsamples {
+                            offsets: subsample_offsets(end - start, &relative, per_pixel),
+                            samples: subsample(band_selected)?,
+                        }),
+                        ..self.sample_buffer(Vec::new(), max_iter, None)
+                    };
+                    pixels.extend(band.colorize_as_part_of(&self.colors, &frame).pixels);
+                }
+                let image = egui::ColorImage { size: [self.width, self.height], pixels };
+                frame.colored = Some((self.colors.clone(), image));
+            }
+            stats.supersampled = selected.len();
+        }
+        Some((RenderedFrame::Samples(Arc::new(frame)), stats))
+    }
+
+    /// Indices of the pixels to supersample, in order, `None` for none.
+    fn supersampled_pixels(&self, samples: &[Sample], max_iter: i32) -> Option<Vec<usize>> {
+        let selected: Vec<usize> = match self.supersampling {
+            Supersampling::Off => return None,
+            Supersampling::Adaptive => (0..samples.len()).filter(|&i| is_edge(samples, self.width, i, max_iter)).collect(),
+            _ => (0..samples.len()).collect(),
+        };
+        (!selected.is_empty()).then_some(selected)
+    }
+
+    fn sample_frame(&self, samples: Vec<Sample>, max_iter: i32, subsamples: Option<Subsamples>) -> RenderedFrame {
+        RenderedFrame::Samples(Arc::new(self.sample_buffer(samples, max_iter, subsamples)))
+    }
+
+    fn sample_buffer(&self, samples: Vec<Sample>, max_iter: i32, subsamples: Option<Subsamples>) -> SampleBuffer {
+        SampleBuffer {
+            width: self.width,
+            height: self.height,
+            samples,
+            subsamples,
+            max_iter,
+            distance_estimated: self.distance_estimation,
+            orbit_trap: self.orbit_trap,
+            interior_analyzed: self.interior_analysis,
+            interior_individual: self.strategy == RenderStrategy::Progressive || self.interior_coloring,
+            colored: None,
+        }
+    }
+
+    /// The rows `top..top + rows` of the frame as a view of their own, for
+    /// rendering frames too large to hold at once. Every band is rendered with
+    /// the iteration limit `max_iter` so they match up.
+    pub fn band(&self, top: usize, rows: usize, max_iter: i32) -> FractalView {
+        // Same pixel size, the center moves down to the middle row of the band
+        let zoom = self.zoom * self.height as f64 / rows as f64;
+        let shift = (top as f64 + rows as f64 / 2.0 - self.height as f64 / 2.0) * self.pixel_size();
+        let frac_bits = BigFixed::precision_for_zoom(zoom).max(self.center_y.frac_bits());
+        FractalView {
+            height: rows,
+            zoom,
+            center_x: self.center_x.with_precision(frac_bits),
+            center_y: self.center_y.with_precision(frac_bits).add_f64(shift),
+            max_iter_policy: MaxIterPolicy { mode: MaxIterMode::Fixed, fixed: max_iter, ..self.max_iter_policy },
+            ..self.clone()
+        }
+    }
+
+    /// Offset of the pixel at (x, y) from the view center in the complex plane,
+    /// fractional positions fall between pixels.
+    fn pixel_offset(&self, x: f64, y: f64) -> (f64, f64) {
+        let aspect_ratio = self.width as f64 / self.height as f64;
+        let height_range = 3.0 / self.zoom;
+        let width_range = height_range * aspect_ratio;
+        let dx = (x / self.width as f64 - 0.5) * width_range;
+        let dy = (y / self.height as f64 - 0.5) * height_range;
+        (dx, dy)
+    }
+
+    fn pixel_size(&self) -> f64 {
+        3.0 / self.zoom / self.height as f64
+    }
+
+    /// Whether pixels are too small for f64 along their orbits and have to be
+    /// iterated by perturbation. Depends on the render height as well as the zoom.
+    pub fn is_deep_zoom(&self) -> bool {
+        let center = self.center_x.to_f64().hypot(self.center_y.to_f64());
+        // Distance between neighbouring orbits after the first step. Julia orbits
+        // start by squaring, near the critical point 0 that shrinks it to 2|z|·pixel.
+        let spacing = match self.fractal_type {
+            FractalType::Julia => 2.0 * (center + 1.5 / self.zoom).min(0.5) * self.pixel_size(),
+            _ => self.pixel_size(),
+        };
+        // Orbits pass |z| ~ 1 on their way out wherever the view is centered
+        spacing < center.max(1.0) * 2f64.powi(-DEEP_ZOOM_PIXEL_BITS)
+    }
+
+    /// Iteration limit of the render under the view's policy, `None` if the
+    /// render was cancelled while probing for it.
+    pub fn max_iterations(&self, cancel: &CancelToken) -> Option<i32> {
+        self.iteration_limit(cancel).map(|(max_iter, _)| max_iter)
+    }
+
+    /// Like `max_iterations`, also handing over the reference orbit of the
+    /// view center if the adaptive policy computed one while probing.
+    fn iteration_limit(&self, cancel: &CancelToken) -> Option<(i32, Option<ReferenceOrbit>)> {
+        let policy = &self.max_iter_policy;
+        match policy.mode {
+            MaxIterMode::Auto => Some((policy.auto(self.zoom), None)),
+            MaxIterMode::Fixed => Some((policy.fixed.max(1), None)),
+            MaxIterMode::Adaptive => self.adaptive_max_iterations(cancel),
+        }
+    }
+
+    /// Double the auto limit until fewer than the policy's percentage of the
+    /// boundary pixels would run into it.
+    ///
+    /// Checked on a sparse grid iterated to twice the candidate limit. Pixels
+    /// escaping after more than half the limit count as boundary, those escaping
+    /// only after the limit would wrongly show as inside the set. Pixels that
+    /// don't escape at all are inside and say nothing about the limit.
+    ///
+    /// At deep zoom the reference orbit is extended with every doubling rather
+    /// than computed anew, and returned for the render to use.
+    fn adaptive_max_iterations(&self, cancel: &CancelToken) -> Option<(i32, Option<ReferenceOrbit>)> {
+        let policy = &self.max_iter_policy;
+        let mut max_iter = policy.auto(self.zoom);
+        let mut reference: Option<ReferenceOrbit> = None;
+        for _ in 0..MAX_ADAPTIVE_DOUBLINGS {
+            let probe_iter = max_iter.saturating_mul(2);
+            reference = match reference.take() {
+                Some(mut reference) => Some(reference.extend(probe_iter, cancel).then_some(reference)?),
+                None if self.is_deep_zoom() => Some(self.reference_orbit((0.0, 0.0), probe_iter, cancel)?),
+                None => None,
+            };
+            let samples = self.probe(probe_iter, reference.as_ref());
+            if cancel.is_cancelled() {
+                return None;
+            }
+            let escaped = samples.iter().filter(|sample| sample.iter < probe_iter);
+            let boundary = escaped.clone().filter(|sample| sample.iter > max_iter / 2).count();
+            let cut_off = escaped.filter(|sample| sample.iter >= max_iter).count();
+            if boundary == 0 || (cut_off as f64) < boundary as f64 * policy.boundary_percent / 100.0 {
+                break;
+            }
+            max_iter = probe_iter;
+        }
+        Some((max_iter, reference))
+    }
+
+    /// Plain samples of every `ADAPTIVE_PROBE_STEP`th pixel, glitched ones left
+    /// out. At deep zoom they are iterated against `reference`.
+    fn probe(&self, max_iter: i32, reference: Option<&ReferenceOrbit>) -> Vec<Sample> {
+        let tracking = OrbitTracking::new(self.fractal_type, self.pixel_size(), false, None, false);
+        let (center_x, center_y) = (self.center_x.to_f64(), self.center_y.to_f64());
+        let pixels: Vec<_> = (0..self.height)
+            .step_by(ADAPTIVE_PROBE_STEP)
+            .flat_map(|y| (0..self.width).step_by(ADAPTIVE_PROBE_STEP).map(move |x| (x, y)))
+            .collect();
+        pixels
+            .par_iter()
+            .filter_map(|&(x, y)| {
+                let (dx, dy) = self.pixel_offset(x as f64, y as f64);
+                match reference {
+                    Some(reference) => {
+                        let pixel = reference.iterate((dx, dy), max_iter, None, tracking);
+                        (!pixel.glitched).then_some(pixel.sample)
+                    }
+                    None => Some(self.escape_time(center_x + dx, center_y + dy, tracking, max_iter)),
+                }
+            })
+            .collect()
+    }
+
+    /// Run the escape-time iteration for one point of the complex plane.
+    fn escape_time(&self, px: f64, py: f64, tracking: OrbitTracking, max_iter: i32) -> Sample {
+        let (z, c) = match self.fractal_type {
+            // Mandelbrot: z starts at 0, c is the pixel coordinate
+            FractalType::Mandelbrot => ((0.0, 0.0), (px, py)),
+            // Julia: z starts at pixel coordinate, c is fixed
+            FractalType::Julia => ((px, py), (self.julia_c_real, self.julia_c_imag)),
+            _ => unreachable!(),
+        };
+
+        // Most of the set's area is in its two largest components, no need to iterate
+        // those. The atom domain is the only thing that needs the actual orbit.
+        if self.fractal_type == FractalType::Mandelbrot
+            && !tracking.tracks_atom_domain()
+            && let Some(cycle) = main_component_cycle(c)
+        {
+            let mut sample = tracking.finish(max_iter, cycle, c);
+            sample.short_circuited = true;
+            return sample;
+        }
+        escape_time_f64(z, c, tracking, 0, max_iter)
+    }
+
+    /// High precision orbit of the point at `offset` from the view center,
+    /// `None` if the render was cancelled while computing it.
+    fn reference_orbit(&self, offset: (f64, f64), max_iter: i32, cancel: &CancelToken) -> Option<ReferenceOrbit> {
+        ReferenceOrbit::compute(
+            self.fractal_type,
+            (&self.center_x, &self.center_y),
+            offset,
+            (self.julia_c_real, self.julia_c_imag),
+            max_iter,
+            cancel,
+        )
+    }
+
+    fn generate_koch_curve(&self, image: &mut egui::ColorImage) {
+        // Generate Koch snowflake with iteration depth based on zoom level
+        let iterations = ((self.zoom.log2() + 1.0).max(0.0) as usize).min(5);
+
+        // Create initial horizontal line segment centered at current view
+        let size = 2.0 / self.zoom;
+
+        let center_x = self.center_x.to_f64();
+        let center_y = self.center_y.to_f64();
+        let p1 = (center_x - size / 2.0, center_y);
+        let p2 = (center_x + size / 2.0, center_y);
+
+        // Generate Koch curve for a single line
+        let mut segments = Vec::new();
+        self.generate_koch_segments(p1, p2, iterations, &mut segments);
+
+        // Draw the segments
+        for (start, end) in segments {
+            self.draw_line(image, start, end);
+        }
+    }
+
+    fn generate_koch_segments(&self, start: (f64, f64), end: (f64, f64), depth: usize, segments: &mut Vec<Segment>) {
+        if depth == 0 {
+            segments.push((start, end));
+            return;
+        }
+
+        // Calculate the four points for Koch curve iteration
+        let dx = end.0 - start.0;
+        let dy = end.1 - start.1;
+
+        // Four key points along the line
+        let p1 = start;
+        let p2 = (start.0 + dx / 3.0, start.1 + dy / 3.0);
+        let p4 = (start.0 + 2.0 * dx / 3.0, start.1 + 2.0 * dy / 3.0);
+        let p5 = end;
+
+        // Calculate the peak point (equilateral triangle bump)
+        let mid_x = (p2.0 + p4.0) / 2.0;
+        let mid_y = (p2.1 + p4.1) / 2.0;
+        let segment_length = (dx * dx + dy * dy).sqrt() / 3.0;
+        let height = segment_length * (3.0_f64.sqrt() / 2.0);
+
+        // Create the bump perpendicular to the line
+        let normal_x = -dy / (dx * dx + dy * dy).sqrt();
+        let normal_y = dx / (dx * dx + dy * dy).sqrt();
+        let p3 = (mid_x + normal_x * height, mid_y + normal_y * height);
+
+        // Recursively generate segments
+        self.generate_koch_segments(p1, p2, depth - 1, segments);
+        self.generate_koch_segments(p2, p3, depth - 1, segments);
+        self.generate_koch_segments(p3, p4, depth - 1, segments);
+        self.generate_koch_segments(p4, p5, depth - 1, segments);
+    }
+
+    fn draw_line(&self, image: &mut egui::ColorImage, start: (f64, f64), end: (f64, f64)) {
+        // Convert world coordinates to screen coordinates
+        let aspect_ratio = self.width as f64 / self.height as f64;
+        let height_range = 3.0 / self.zoom;
+        let width_range = height_range * aspect_ratio;
+
+        let left = self.center_x.to_f64() - width_range / 2.0;
+        let right = self.center_x.to_f64() + width_range / 2.0;
+        let top = self.center_y.to_f64() - height_range / 2.0;
+        let bottom = self.center_y.to_f64() + height_range / 2.0;
+
+        let sx = ((start.0 - left) / (right - left) * self.width as f64) as i32;
+        let sy = ((start.1 - top) / (bottom - top) * self.height as f64) as i32;
+        let ex = ((end.0 - left) / (right - left) * self.width as f64) as i32;
+        let ey = ((end.1 - top) / (bottom - top) * self.height as f64) as i32;
+
+        // Simple line drawing with thick lines for better visibility
+        let dx = (ex - sx).abs();
+        let dy = (ey - sy).abs();
+        let steps = dx.max(dy).max(1);
+
+        for i in 0..=steps {
+            let t = i as f64 / steps as f64;
+            let x = (sx as f64 + t * (ex - sx) as f64) as i32;
+            let y = (sy as f64 + t * (ey - sy) as f64) as i32;
+
+            // Draw thick line (3x3 pixels)
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let px = x + dx;
+                    let py = y + dy;
+                    if px >= 0 && px < self.width as i32 && py >= 0 && py < self.height as i32 {
+                        let index = py as usize * image.width() + px as usize;
+                        // Use bright green color for Koch curve
+                        image.pixels[index] = egui::Color32::from_rgb(0, 255, 0);
+                    }
+                }
+            }
+        }
+    }
+}
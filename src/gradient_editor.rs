@@ -0,0 +1,107 @@
+use eframe::egui;
+
+use crate::palette::{ColorStop, Palette};
+
+const STRIP_HEIGHT: f32 = 24.0;
+const HANDLE_SIZE: f32 = 7.0;
+
+/// Side panel widget for editing the color stops of a palette.
+///
+/// Shows the gradient as a strip with a draggable handle below every stop.
+/// Clicking a handle selects its stop for recoloring or deletion, double
+/// clicking the strip adds a stop there. Returns true if the stops changed.
+pub fn gradient_editor(ui: &mut egui::Ui, palette: &mut Palette) -> bool {
+    let selected_id = ui.id().with("gradient_editor_selected");
+    let mut selected: Option<usize> = ui.data(|data| data.get_temp(selected_id)).flatten();
+    selected = selected.filter(|&i| i < palette.stops.len());
+    let mut changed = false;
+
+    let width = ui.available_width();
+    let (strip_rect, strip_response) =
+        ui.allocate_exact_size(egui::vec2(width, STRIP_HEIGHT), egui::Sense::click());
+    let painter = ui.painter_at(strip_rect.expand2(egui::vec2(HANDLE_SIZE, 0.0)));
+
+    // One column per point of width is plenty for a preview
+    let columns = strip_rect.width().max(1.0) as usize;
+    for i in 0..columns {
+        let u = i as f32 / (columns - 1).max(1) as f32;
+        let x = strip_rect.left() + i as f32;
+        painter.rect_filled(
+            egui::Rect::from_min_max(egui::pos2(x, strip_rect.top()), egui::pos2(x + 1.0, strip_rect.bottom())),
+            0.0,
+            palette.gradient_at(u),
+        );
+    }
+    painter.rect_stroke(strip_rect, 0.0, ui.visuals().widgets.noninteractive.bg_stroke);
+
+    let position_at = |x: f32| ((x - strip_rect.left()) / strip_rect.width()).clamp(0.0, 1.0);
+
+    if strip_response.double_clicked()
+        && let Some(pointer) = strip_response.interact_pointer_pos()
+    {
+        let position = position_at(pointer.x);
+        let color = palette.gradient_at(position);
+        let index = palette.stops.partition_point(|stop| stop.position <= position);
+        palette.stops.insert(index, ColorStop { position, color });
+        selected = Some(index);
+        changed = true;
+    }
+
+    // Handles below the strip, one per stop
+    let (handle_rect, _) = ui.allocate_exact_size(egui::vec2(width, HANDLE_SIZE * 2.0), egui::Sense::hover());
+    for i in 0..palette.stops.len() {
+        let x = strip_rect.left() + palette.stops[i].position * strip_rect.width();
+        let rect = egui::Rect::from_center_size(
+            egui::pos2(x, handle_rect.center().y),
+            egui::vec2(HANDLE_SIZE * 2.0, HANDLE_SIZE * 2.0),
+        );
+        let response = ui.interact(rect, selected_id.with(i), egui::Sense::click_and_drag());
+        if response.clicked() || response.drag_started() {
+            selected = Some(i);
+        }
+        if response.dragged()
+            && let Some(pointer) = response.interact_pointer_pos()
+        {
+            // Stay between the neighbours so the stops remain sorted
+            let low = if i > 0 { palette.stops[i - 1].position } else { 0.0 };
+            let high = palette.stops.get(i + 1).map_or(1.0, |stop| stop.position);
+            let position = position_at(pointer.x).clamp(low, high);
+            if position != palette.stops[i].position {
+                palette.stops[i].position = position;
+                changed = true;
+            }
+        }
+
+        let stop = palette.stops[i];
+        let x = strip_rect.left() + stop.position * strip_rect.width();
+        let top = handle_rect.top();
+        let points = vec![
+            egui::pos2(x, top),
+            egui::pos2(x + HANDLE_SIZE, top + HANDLE_SIZE * 1.7),
+            egui::pos2(x - HANDLE_SIZE, top + HANDLE_SIZE * 1.7),
+        ];
+        let stroke = if selected == Some(i) {
+            egui::Stroke::new(2.0, ui.visuals().selection.stroke.color)
+        } else {
+            egui::Stroke::new(1.0, ui.visuals().widgets.inactive.fg_stroke.color)
+        };
+        ui.painter().add(egui::Shape::convex_polygon(points, stop.color, stroke));
+    }
+
+    if let Some(i) = selected {
+        ui.horizontal(|ui| {
+            ui.label(format!("Stop at {:.2}", palette.stops[i].position));
+            changed |= ui.color_edit_button_srgba(&mut palette.stops[i].color).changed();
+            let can_delete = palette.stops.len() > 2;
+            if ui.add_enabled(can_delete, egui::Button::new("Delete")).clicked() {
+                palette.stops.remove(i);
+                selected = None;
+                changed = true;
+            }
+        });
+    }
+    ui.weak("Double-click to add a stop, drag to move");
+
+    ui.data_mut(|data| data.insert_temp(selected_id, selected));
+    changed
+}
@@ -0,0 +1,69 @@
+use num::complex::Complex64;
+
+use crate::fractal::FractalType;
+
+/// Longest attracting cycle looked for inside the set.
+const MAX_PERIOD: usize = 1024;
+
+/// How close the orbit has to come back to where it started to be a candidate
+/// cycle. Loose, near the boundary orbits converge very slowly, Newton's
+/// method then pins the cycle down exactly.
+const CYCLE_TOLERANCE: f64 = 1e-6;
+
+/// Newton steps spent refining a candidate cycle point.
+const NEWTON_STEPS: usize = 8;
+
+/// What is known about the attracting cycle of a point that never escaped.
+pub struct Interior {
+    pub period: i32,
+    /// Interior distance estimate to the boundary of the component, in the
+    /// units of the complex plane. Only worked out for the Mandelbrot set.
+    pub distance: Option<f64>,
+}
+
+/// Analyze the cycle the orbit at `z` has settled into after its iterations ran out.
+///
+/// Returns `None` when the orbit has not converged closely enough to find the
+/// cycle, which happens near the boundary where convergence gets slow.
+pub fn analyze(fractal_type: FractalType, z: (f64, f64), c: (f64, f64)) -> Option<Interior> {
+    let start = Complex64::new(z.0, z.1);
+    let c = Complex64::new(c.0, c.1);
+
+    let mut z = start;
+    let period = (1..=MAX_PERIOD).find(|_| {
+        z = z * z + c;
+        (z - start).norm_sqr() < CYCLE_TOLERANCE * CYCLE_TOLERANCE
+    })?;
+
+    // Solve f^p(z) = z near the candidate, the cycle is attracting only if |(f^p)'| < 1
+    let mut cycle = start;
+    for _ in 0..NEWTON_STEPS {
+        let (mut z, mut dz) = (cycle, Complex64::new(1.0, 0.0));
+        for _ in 0..period {
+            dz = z * dz * 2.0;
+            z = z * z + c;
+        }
+        if dz.norm_sqr() >= 1.0 {
+            return None;
+        }
+        cycle -= (z - cycle) / (dz - 1.0);
+    }
+
+    let distance = (fractal_type == FractalType::Mandelbrot).then(|| {
+        // Derivatives of the p-fold iterate at the cycle point: with respect to
+        // z, c, twice to z and to c and z
+        let mut z = cycle;
+        let (mut dz, mut dc) = (Complex64::new(1.0, 0.0), Complex64::new(0.0, 0.0));
+        let (mut dzz, mut dcz) = (Complex64::new(0.0, 0.0), Complex64::new(0.0, 0.0));
+        for _ in 0..period {
+            dcz = (z * dcz + dz * dc) * 2.0;
+            dzz = (dz * dz + z * dzz) * 2.0;
+            dz = z * dz * 2.0;
+            dc = z * dc * 2.0 + 1.0;
+            z = z * z + c;
+        }
+        (1.0 - dz.norm_sqr()) / (dcz + dzz * dc / (-dz + 1.0)).norm()
+    });
+
+    Some(Interior { period: period as i32, distance })
+}
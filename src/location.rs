@@ -0,0 +1,164 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::bigfixed::BigFixed;
+use crate::fractal::{FractalType, FractalView, MAX_ZOOM};
+use crate::palette::{Interpolation, Palette, RepeatMode};
+
+/// Where in which fractal a render was made and how, enough to get back to
+/// the same view. Exported PNGs carry it in their text chunks.
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub fractal_type: FractalType,
+    pub center_x: BigFixed,
+    pub center_y: BigFixed,
+    pub zoom: f64,
+    pub julia_c: (f64, f64),
+    pub max_iter: i32,
+    pub palette: Palette,
+}
+
+impl Location {
+    pub fn of(view: &FractalView, max_iter: i32, palette: &Palette) -> Self {
+        Self {
+            fractal_type: view.fractal_type,
+            center_x: view.center_x.clone(),
+            center_y: view.center_y.clone(),
+            zoom: view.zoom,
+            julia_c: (view.julia_c_real, view.julia_c_imag),
+            max_iter,
+            palette: palette.clone(),
+        }
+    }
+
+    /// Whether a zoom read back from a file is one the view can be set to.
+    pub fn valid_zoom(zoom: f64) -> bool {
+        zoom > 0.0 && zoom <= MAX_ZOOM
+    }
+
+    /// Keyword and text pairs for PNG text chunks.
+    ///
+    /// The center is written with every digit its precision resolves and the
+    /// zoom in Rust's shortest round-trip form, so reading them back gives the same view.
+    pub fn to_text(&self) -> Vec<(&'static str, String)> {
+        let palette = &self.palette;
+        vec![
+            ("Software", "Fractal Explorer".to_owned()),
+            ("Fractal", self.fractal_type.as_str().to_owned()),
+            ("Center X", self.center_x.to_string()),
+            ("Center Y", self.center_y.to_string()),
+            ("Zoom", format!("{:e}", self.zoom)),
+            ("Julia C", format!("{} {}", self.julia_c.0, self.julia_c.1)),
+            ("Max Iterations", self.max_iter.to_string()),
+            ("Palette", palette.name.clone()),
+            ("Palette Stops", palette.stops_text()),
+            ("Palette Interpolation", palette.interpolation.as_str().to_owned()),
+            ("Palette Repeat", palette.repeat.as_str().to_owned()),
+            ("Palette Offset", palette.offset.to_string()),
+            ("Palette Scale", palette.scale.to_string()),
+        ]
+    }
+
+    /// Inverse of `to_text`, the error names what is missing or malformed.
+    pub fn from_text(text: &[(String, String)]) -> Result<Self, String> {
+        let get = |key: &str| {
+            text.iter()
+                .find(|(keyword, _)| keyword == key)
+                .map(|(_, value)| value.as_str())
+                .ok_or_else(|| format!("No {key} in the image"))
+        };
+        let invalid = |key: &str| format!("Invalid {key} in the image");
+        let number = |key: &str| get(key)?.trim().parse::<f64>().map_err(|_| invalid(key));
+        let pair = |key: &str| {
+            let value = get(key)?;
+            let mut parts = value.split_whitespace().map(str::parse::<f64>);
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(Ok(a)), Some(Ok(b)), None) => Ok((a, b)),
+                _ => Err(invalid(key)),
+            }
+        };
+        let named = |key: &str, names: &[&'static str]| {
+            let value = get(key)?;
+            names.iter().position(|name| *name == value).ok_or_else(|| invalid(key))
+        };
+
+        let fractal_type = FractalType::ALL[named("Fractal", &FractalType::ALL.map(|t| t.as_str()))?];
+        let zoom = number("Zoom")?;
+        if !Self::valid_zoom(zoom) {
+            return Err(invalid("Zoom"));
+        }
+        let frac_bits = BigFixed::precision_for_zoom(zoom);
+        let center = |key: &str| BigFixed::parse(get(key)?, frac_bits).ok_or_else(|| invalid(key));
+        let max_iter = get("Max Iterations")?.trim().parse().map_err(|_| invalid("Max Iterations"))?;
+
+        let palette = Palette {
+            name: get("Palette")?.to_owned(),
+            stops: Palette::parse_stops(get("Palette Stops")?).ok_or_else(|| invalid("Palette Stops"))?,
+            interpolation: Interpolation::ALL[named("Palette Interpolation", &Interpolation::ALL.map(|i| i.as_str()))?],
+            repeat: RepeatMode::ALL[named("Palette Repeat", &RepeatMode::ALL.map(|r| r.as_str()))?],
+            offset: number("Palette Offset")? as f32,
+            scale: number("Palette Scale")? as f32,
+        };
+
+        Ok(Self {
+            fractal_type,
+            center_x: center("Center X")?,
+            center_y: center("Center Y")?,
+            zoom,
+            julia_c: pair("Julia C")?,
+            max_iter,
+            palette,
+        })
+    }
+
+    /// Read the location from the text chunks of a PNG exported earlier.
+    pub fn read_png(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let reader = png::Decoder::new(BufReader::new(file))
+            .read_info()
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        let text: Vec<_> = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+            .collect();
+        Self::from_text(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let frac_bits = BigFixed::precision_for_zoom(3e20);
+        let location = Location {
+            fractal_type: FractalType::Julia,
+            center_x: BigFixed::parse("-1.74970000000001234567890123", frac_bits).unwrap(),
+            center_y: BigFixed::parse("0.00001", frac_bits).unwrap(),
+            zoom: 3e20,
+            julia_c: (-0.8, 0.156),
+            max_iter: 12345,
+            palette: Palette {
+                interpolation: Interpolation::Smooth,
+                repeat: RepeatMode::Mirror,
+                offset: 0.3,
+                scale: 1.7,
+                ..Palette::twilight()
+            },
+        };
+        let text: Vec<_> = location.to_text().into_iter().map(|(keyword, text)| (keyword.to_owned(), text)).collect();
+        let read = Location::from_text(&text).unwrap();
+
+        assert_eq!(read.fractal_type, location.fractal_type);
+        assert_eq!(read.center_x, location.center_x);
+        assert_eq!(read.center_y, location.center_y);
+        assert_eq!(read.zoom, location.zoom);
+        assert_eq!(read.julia_c, location.julia_c);
+        assert_eq!(read.max_iter, location.max_iter);
+        assert_eq!(read.palette, location.palette);
+    }
+}
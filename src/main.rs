@@ -1,31 +1,64 @@
 use eframe::{egui, App, Frame};
 
-const WIDTH: u32 = 1200;
-const HEIGHT: u32 = 800;
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum FractalType {
-    Mandelbrot,
-    Julia,
-    Koch,
-}
-
-impl FractalType {
-    fn as_str(&self) -> &'static str {
-        match self {
-            FractalType::Mandelbrot => "Mandelbrot Set",
-            FractalType::Julia => "Julia Set",
-            FractalType::Koch => "Koch Curve",
-        }
-    }
-}
+mod bigfixed;
+mod coloring;
+mod export;
+mod fractal;
+mod gradient_editor;
+mod interior;
+mod location;
+mod orbit_trap;
+mod palette;
+mod perturbation;
+mod session;
+mod subdivision;
+mod worker;
+
+use bigfixed::BigFixed;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use coloring::{ColorSettings, ColoringMode, InteriorMode, SampleBuffer};
+use export::Export;
+use location::Location;
+use fractal::{FractalType, FractalView, MaxIterMode, MaxIterPolicy, RenderStats, RenderStrategy, RenderedFrame, Supersampling, MAX_ZOOM};
+use orbit_trap::{OrbitTrap, TrapShape};
+use palette::{Interpolation, Palette, RepeatMode};
+use session::Session;
+use worker::{default_thread_count, RenderWorker};
+
+/// Points of scroll delta egui reports for one mouse wheel notch.
+const SCROLL_NOTCH: f64 = 50.0;
 
 struct MandelbrotApp {
     texture: Option<egui::TextureHandle>,
+    // Samples behind the texture, kept so color changes only recolor them
+    samples: Option<Arc<SampleBuffer>>,
+    // Views the samples and the texture were rendered for. Until a frame of the
+    // current view arrives the texture is drawn moved and scaled onto it.
+    samples_view: Option<FractalView>,
+    texture_view: Option<FractalView>,
+    needs_recolor: bool,
+    renderer: RenderWorker,
+    render_threads: usize,
+    last_render_time: Option<std::time::Duration>,
+    render_stats: RenderStats,
+    series_approximation: bool,
+    render_strategy: RenderStrategy,
+    fill_guard: bool,
+    max_iter_policy: MaxIterPolicy,
+    supersampling: Supersampling,
     // Viewport parameters for zoom and pan
-    center_x: f64,
-    center_y: f64,
+    center_x: BigFixed,
+    center_y: BigFixed,
     zoom: f64,
+    // Zoom factor of one mouse wheel notch
+    zoom_step: f64,
+    smooth_zoom: bool,
+    // A smoothed wheel notch is still playing out, the render waits for where it ends
+    zoom_animating: bool,
+    // Size of the rendered frame in pixels, follows the central panel
+    render_size: [usize; 2],
     // Mouse interaction state
     dragging: bool,
     last_mouse_pos: Option<egui::Pos2>,
@@ -36,25 +69,62 @@ struct MandelbrotApp {
     selecting_zoom_rect: bool,
     // Fractal type and Julia set parameters
     fractal_type: FractalType,
+    colors: ColorSettings,
+    orbit_trap: OrbitTrap,
     julia_c_real: f64,
     julia_c_imag: f64,
+    // Image export
+    export_size: [usize; 2],
+    export_supersampling: Supersampling,
+    image_path: String,
+    export: Option<Export>,
+    image_message: Option<String>,
+    // Location files
+    session_path: String,
+    session_message: Option<String>,
     // UI state
     show_controls: bool,
 }
 
-impl Default for MandelbrotApp {
-    fn default() -> Self {
+impl MandelbrotApp {
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
         Self {
             texture: None,
-            center_x: -0.5,  // Center on the main body of the Mandelbrot set
-            center_y: 0.0,
+            samples: None,
+            samples_view: None,
+            texture_view: None,
+            needs_recolor: false,
+            renderer: RenderWorker::spawn(cc.egui_ctx.clone()),
+            render_threads: default_thread_count(),
+            last_render_time: None,
+            render_stats: RenderStats::default(),
+            series_approximation: true,
+            render_strategy: RenderStrategy::Progressive,
+            fill_guard: true,
+            max_iter_policy: MaxIterPolicy::default(),
+            supersampling: Supersampling::Off,
+            center_x: BigFixed::from_f64(-0.5, BigFixed::precision_for_zoom(1.0)),  // Center on the main body of the Mandelbrot set
+            center_y: BigFixed::from_f64(0.0, BigFixed::precision_for_zoom(1.0)),
             zoom: 1.0,
+            zoom_step: 1.1,
+            smooth_zoom: true,
+            zoom_animating: false,
+            render_size: [1200, 800],
             dragging: false,
             last_mouse_pos: None,
             needs_redraw: true,
             fractal_type: FractalType::Mandelbrot,
+            colors: FractalType::Mandelbrot.default_colors(),
+            orbit_trap: OrbitTrap::default(),
             julia_c_real: -0.7269,  // Interesting Julia set constant
             julia_c_imag: 0.1889,
+            export_size: [3600, 2400],
+            export_supersampling: Supersampling::Off,
+            image_path: "fractal.png".to_owned(),
+            export: None,
+            image_message: None,
+            session_path: "location.toml".to_owned(),
+            session_message: None,
             show_controls: true,
             zoom_rect_start: None,
             zoom_rect_end: None,
@@ -93,23 +163,184 @@ impl App for MandelbrotApp {
                     self.needs_redraw = true;
                     // Reset view when switching fractal types
                     if self.fractal_type == FractalType::Mandelbrot {
-                        self.center_x = -0.5;
-                        self.center_y = 0.0;
-                        self.zoom = 1.0;
+                        self.set_view(-0.5, 0.0, 1.0);
                     } else if self.fractal_type == FractalType::Julia {
-                        self.center_x = 0.0;
-                        self.center_y = 0.0;
-                        self.zoom = 1.5;
+                        self.set_view(0.0, 0.0, 1.5);
                     } else {
                         // Koch curve
-                        self.center_x = 0.0;
-                        self.center_y = -0.2;
-                        self.zoom = 0.8;
+                        self.set_view(0.0, -0.2, 0.8);
                     }
                 }
 
                 ui.separator();
 
+                // Coloring only applies to the escape-time fractals
+                if self.fractal_type != FractalType::Koch {
+                    let colors_before = self.colors.clone();
+                    ui.label("Coloring:");
+                    egui::ComboBox::from_id_source("coloring_mode")
+                        .selected_text(self.colors.mode.as_str())
+                        .show_ui(ui, |ui| {
+                            for mode in ColoringMode::ALL {
+                                ui.selectable_value(&mut self.colors.mode, mode, mode.as_str());
+                            }
+                        });
+                    if self.colors.mode == ColoringMode::Boundary {
+                        ui.add(egui::Slider::new(&mut self.colors.line_thickness, 0.25..=10.0)
+                            .logarithmic(true)
+                            .text("line thickness (px)"));
+                    } else {
+                        ui.add_enabled(
+                            self.colors.mode != ColoringMode::Histogram,
+                            egui::Slider::new(&mut self.colors.gamma, 0.1..=3.0)
+                                .logarithmic(true)
+                                .text("gamma"),
+                        );
+                    }
+
+                    egui::ComboBox::from_label("interior")
+                        .selected_text(self.colors.interior.as_str())
+                        .show_ui(ui, |ui| {
+                            for mode in InteriorMode::ALL {
+                                ui.selectable_value(&mut self.colors.interior, mode, mode.as_str());
+                            }
+                        });
+
+                    ui.label("Palette:");
+                    egui::ComboBox::from_id_source("palette")
+                        .selected_text(self.colors.palette.name.as_str())
+                        .show_ui(ui, |ui| {
+                            for preset in Palette::presets() {
+                                if ui.selectable_label(self.colors.palette.name == preset.name, preset.name.as_str()).clicked() {
+                                    self.colors.palette = preset;
+                                }
+                            }
+                        });
+                    egui::ComboBox::from_label("interpolation")
+                        .selected_text(self.colors.palette.interpolation.as_str())
+                        .show_ui(ui, |ui| {
+                            for mode in Interpolation::ALL {
+                                ui.selectable_value(&mut self.colors.palette.interpolation, mode, mode.as_str());
+                            }
+                        });
+                    egui::ComboBox::from_label("repeat")
+                        .selected_text(self.colors.palette.repeat.as_str())
+                        .show_ui(ui, |ui| {
+                            for mode in RepeatMode::ALL {
+                                ui.selectable_value(&mut self.colors.palette.repeat, mode, mode.as_str());
+                            }
+                        });
+                    ui.add(egui::Slider::new(&mut self.colors.palette.scale, 0.1..=20.0)
+                        .logarithmic(true)
+                        .text("scale"));
+                    ui.add(egui::Slider::new(&mut self.colors.palette.offset, 0.0..=1.0)
+                        .text("offset"));
+                    if gradient_editor::gradient_editor(ui, &mut self.colors.palette) {
+                        self.colors.palette.name = "Custom".to_owned();
+                    }
+
+                    let lighting = &mut self.colors.lighting;
+                    ui.checkbox(&mut lighting.enabled, "3D lighting");
+                    if lighting.enabled {
+                        ui.add(egui::Slider::new(&mut lighting.azimuth, 0.0..=360.0)
+                            .suffix("°")
+                            .text("azimuth"));
+                        ui.add(egui::Slider::new(&mut lighting.elevation, 0.0..=90.0)
+                            .suffix("°")
+                            .text("elevation"));
+                        ui.add(egui::Slider::new(&mut lighting.height, 0.1..=10.0)
+                            .logarithmic(true)
+                            .text("height"));
+                    }
+                    if ui.button("Reset colors").on_hover_text("Default coloring of this fractal type").clicked() {
+                        self.colors = self.fractal_type.default_colors();
+                    }
+                    if self.colors != colors_before {
+                        // Distance estimates and traps are only computed when a mode asks for them,
+                        // frames with too many subsamples to keep only hold their colors
+                        if !self.samples.as_ref().is_some_and(|samples| samples.supports(&self.colors)) {
+                            self.needs_redraw = true;
+                        }
+                        self.needs_recolor = true;
+                    }
+
+                    if self.colors.mode.uses_trap() {
+                        ui.separator();
+                        ui.label("Orbit Trap:");
+                        let trap_before = self.orbit_trap;
+                        let trap = &mut self.orbit_trap;
+                        egui::ComboBox::from_label("shape")
+                            .selected_text(trap.shape.as_str())
+                            .show_ui(ui, |ui| {
+                                for shape in TrapShape::ALL {
+                                    ui.selectable_value(&mut trap.shape, shape, shape.as_str());
+                                }
+                            });
+                        ui.horizontal(|ui| {
+                            ui.label("Center:");
+                            ui.add(egui::DragValue::new(&mut trap.center.0).speed(0.01));
+                            ui.add(egui::DragValue::new(&mut trap.center.1).speed(0.01));
+                        });
+                        match trap.shape {
+                            TrapShape::Line | TrapShape::Cross => {
+                                ui.add(egui::Slider::new(&mut trap.angle, 0.0..=180.0)
+                                    .suffix("°")
+                                    .text("angle"));
+                            }
+                            TrapShape::Circle => {
+                                ui.add(egui::Slider::new(&mut trap.radius, 0.01..=2.0)
+                                    .logarithmic(true)
+                                    .text("radius"));
+                            }
+                            TrapShape::Point => {}
+                        }
+                        ui.add(egui::Slider::new(&mut trap.size, 0.001..=1.0)
+                            .logarithmic(true)
+                            .text("size"));
+                        ui.weak("Drag the trap marker on the image to move it");
+                        if self.orbit_trap != trap_before {
+                            self.needs_redraw = true;
+                        }
+                    }
+
+                    ui.separator();
+                    ui.label("Max Iterations:");
+                    let policy_before = self.max_iter_policy;
+                    let policy = &mut self.max_iter_policy;
+                    egui::ComboBox::from_id_source("max_iter_mode")
+                        .selected_text(policy.mode.as_str())
+                        .show_ui(ui, |ui| {
+                            for mode in MaxIterMode::ALL {
+                                ui.selectable_value(&mut policy.mode, mode, mode.as_str());
+                            }
+                        });
+                    match policy.mode {
+                        MaxIterMode::Auto => {
+                            ui.horizontal(|ui| {
+                                ui.add(egui::DragValue::new(&mut policy.base).speed(1.0).clamp_range(1.0..=f64::MAX));
+                                ui.label("+");
+                                ui.add(egui::DragValue::new(&mut policy.per_decade).speed(1.0).clamp_range(0.0..=f64::MAX));
+                                ui.label("× log10(zoom)");
+                            });
+                            ui.weak(format!("{} at this zoom", policy.auto(self.zoom)));
+                        }
+                        MaxIterMode::Fixed => {
+                            ui.add(egui::DragValue::new(&mut policy.fixed).speed(10.0).clamp_range(1..=i32::MAX));
+                        }
+                        MaxIterMode::Adaptive => {
+                            ui.add(egui::Slider::new(&mut policy.boundary_percent, 0.1..=10.0)
+                                .logarithmic(true)
+                                .suffix("%")
+                                .text("boundary pixels at limit"));
+                        }
+                    }
+                    if self.max_iter_policy != policy_before {
+                        self.needs_redraw = true;
+                    }
+
+                    ui.separator();
+                }
+
                 // Julia set parameters (only show for Julia set)
                 if self.fractal_type == FractalType::Julia {
                     ui.label("Julia Set Parameters:");
@@ -159,29 +390,190 @@ impl App for MandelbrotApp {
                 // Current view info
                 ui.label("Current View:");
                 ui.label(format!("Zoom: {:.2e}", self.zoom));
-                ui.label(format!("Center: ({:.6}, {:.6})", self.center_x, self.center_y));
+                // Show as many digits as the zoom level can distinguish
+                let digits = 6 + self.zoom.log10().max(0.0) as usize;
+                ui.label(format!("Center: ({:.digits$}, {:.digits$})", self.center_x, self.center_y));
+                if self.renderer.is_busy() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Rendering...");
+                    });
+                } else if let Some(elapsed) = self.last_render_time {
+                    ui.label(format!("Render time: {} ms", elapsed.as_millis()));
+                    if self.series_approximation && self.view().is_deep_zoom() {
+                        ui.label(format!("Series approximation skipped {} iterations", self.render_stats.series_skipped));
+                    }
+                    let stats = &self.render_stats;
+                    if stats.max_iter > 0 {
+                        ui.label(format!("Max iterations: {}", stats.max_iter));
+                    }
+                    if stats.pixels > 0 {
+                        let percent = 100.0 * stats.short_circuited as f64 / stats.pixels as f64;
+                        ui.label(format!("Interior short-circuited: {percent:.1}% of pixels"));
+                        if self.render_strategy == RenderStrategy::RectangleSubdivision {
+                            let percent = 100.0 * stats.filled as f64 / stats.pixels as f64;
+                            ui.label(format!("Filled without iterating: {percent:.1}% of pixels"));
+                        }
+                        if self.supersampling == Supersampling::Adaptive {
+                            let percent = 100.0 * stats.supersampled as f64 / stats.pixels as f64;
+                            ui.label(format!("Supersampled: {percent:.1}% of pixels"));
+                        }
+                    }
+                }
+
+                ui.separator();
+                ui.label("Performance:");
+                let max_threads = (default_thread_count() * 2).max(2);
+                if ui.add(egui::Slider::new(&mut self.render_threads, 1..=max_threads)
+                    .text("threads")).changed() {
+                    self.needs_redraw = true;
+                }
+                if ui.checkbox(&mut self.series_approximation, "Series approximation (deep zoom)").changed() {
+                    self.needs_redraw = true;
+                }
+                let supersampling_before = self.supersampling;
+                egui::ComboBox::from_label("supersampling")
+                    .selected_text(self.supersampling.as_str())
+                    .show_ui(ui, |ui| {
+                        for supersampling in Supersampling::ALL {
+                            ui.selectable_value(&mut self.supersampling, supersampling, supersampling.as_str());
+                        }
+                    });
+                if self.supersampling != supersampling_before {
+                    self.needs_redraw = true;
+                }
+                let strategy_before = self.render_strategy;
+                egui::ComboBox::from_label("render mode")
+                    .selected_text(self.render_strategy.as_str())
+                    .show_ui(ui, |ui| {
+                        for strategy in RenderStrategy::ALL {
+                            ui.selectable_value(&mut self.render_strategy, strategy, strategy.as_str());
+                        }
+                    });
+                if self.render_strategy != strategy_before {
+                    self.needs_redraw = true;
+                }
+                if self.render_strategy == RenderStrategy::RectangleSubdivision
+                    && ui.checkbox(&mut self.fill_guard, "Only fill inside the set")
+                        .on_hover_text("Turn off to fill any rectangle with a uniform border, faster but flattens the colors outside the set")
+                        .changed()
+                {
+                    self.needs_redraw = true;
+                }
+
+                ui.separator();
+                ui.label("Export:");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.export_size[0]).clamp_range(1..=65535).suffix(" px"));
+                    ui.label("×");
+                    ui.add(egui::DragValue::new(&mut self.export_size[1]).clamp_range(1..=65535).suffix(" px"));
+                    if ui.button("Window aspect").clicked() {
+                        let [width, height] = self.render_size;
+                        self.export_size[1] = (self.export_size[0] * height / width).max(1);
+                    }
+                });
+                egui::ComboBox::from_label("export supersampling")
+                    .selected_text(self.export_supersampling.as_str())
+                    .show_ui(ui, |ui| {
+                        for supersampling in Supersampling::ALL {
+                            ui.selectable_value(&mut self.export_supersampling, supersampling, supersampling.as_str());
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.image_path);
+                });
+                if let Some(result) = self.export.as_ref().and_then(Export::finished) {
+                    let path = self.export.take().unwrap().path;
+                    self.image_message = Some(match result {
+                        Ok(()) => format!("Saved {}", path.display()),
+                        Err(err) => err,
+                    });
+                }
+                if let Some(export) = &self.export {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::ProgressBar::new(export.progress()).show_percentage().desired_width(150.0));
+                        if ui.button("Cancel").clicked() {
+                            export.cancel();
+                        }
+                    });
+                } else if ui.add_enabled(!self.renderer.is_busy(), egui::Button::new("Export image…"))
+                    .on_disabled_hover_text("Wait for the current render to finish")
+                    .clicked()
+                {
+                    let [width, height] = self.export_size;
+                    let current = self.view();
+                    // The samples on screen lag behind while a smooth zoom settles, only
+                    // share them when they are of the same view at another size
+                    let screen = self.samples.clone().filter(|_| {
+                        self.samples_view.clone().is_some_and(|shown| {
+                            let (width, height) = (current.width, current.height);
+                            FractalView { width, height, supersampling: current.supersampling, colors: current.colors.clone(), ..shown } == current
+                        })
+                    });
+                    let view = FractalView { width, height, supersampling: self.export_supersampling, ..current };
+                    let path = PathBuf::from(&self.image_path);
+                    self.export = Some(Export::start(ctx.clone(), view, self.colors.clone(), screen, path, self.render_threads));
+                    self.image_message = None;
+                }
+                if ui.button("Open location from image").clicked() {
+                    match Location::read_png(Path::new(&self.image_path)) {
+                        Ok(location) => {
+                            self.open_location(location);
+                            self.image_message = None;
+                        }
+                        Err(err) => self.image_message = Some(err),
+                    }
+                }
+                if let Some(message) = &self.image_message {
+                    ui.label(message);
+                }
+
+                ui.separator();
+                ui.label("Location file:");
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.session_path);
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Save location").clicked() {
+                        let path = Path::new(&self.session_path);
+                        self.session_message = Some(match self.session().save(path) {
+                            Ok(()) => format!("Saved {}", path.display()),
+                            Err(err) => err,
+                        });
+                    }
+                    if ui.button("Open location").clicked() {
+                        self.open_session_file();
+                    }
+                });
+                if let Some(message) = &self.session_message {
+                    ui.label(message);
+                }
+
+                ui.separator();
+                ui.label("Navigation:");
+                ui.add(egui::Slider::new(&mut self.zoom_step, 1.01..=4.0)
+                    .logarithmic(true)
+                    .text("zoom per wheel notch"));
+                ui.checkbox(&mut self.smooth_zoom, "Smooth zoom")
+                    .on_hover_text("Animate wheel zooming and render once a notch has played out");
 
                 ui.separator();
                 ui.label("Controls:");
-                ui.label("• Mouse wheel: Zoom");
+                ui.label("• Mouse wheel / pinch: Zoom at the pointer");
                 ui.label("• Click & drag: Pan");
                 ui.label("• Shift + drag: Zoom to rectangle");
                 ui.label("• Tab: Toggle this panel");
 
                 if ui.button("Reset View").clicked() {
                     if self.fractal_type == FractalType::Mandelbrot {
-                        self.center_x = -0.5;
-                        self.center_y = 0.0;
-                        self.zoom = 1.0;
+                        self.set_view(-0.5, 0.0, 1.0);
                     } else if self.fractal_type == FractalType::Julia {
-                        self.center_x = 0.0;
-                        self.center_y = 0.0;
-                        self.zoom = 1.5;
+                        self.set_view(0.0, 0.0, 1.5);
                     } else {
                         // Koch curve
-                        self.center_x = 0.0;
-                        self.center_y = -0.2;
-                        self.zoom = 0.8;
+                        self.set_view(0.0, -0.2, 0.8);
                     }
                     self.needs_redraw = true;
                 }
@@ -195,7 +587,7 @@ impl App for MandelbrotApp {
         // Handle keyboard navigation for zooming
         if ctx.input(|i| i.key_pressed(egui::Key::Plus) || i.key_pressed(egui::Key::Equals)) {
             // Zoom in with + or = key
-            self.zoom *= 1.5;
+            self.zoom = (self.zoom * 1.5).min(MAX_ZOOM);
             self.needs_redraw = true;
         }
         if ctx.input(|i| i.key_pressed(egui::Key::Minus)) {
@@ -209,19 +601,19 @@ impl App for MandelbrotApp {
         let mut pan_changed = false;
 
         if ctx.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
-            self.center_x -= pan_distance;
+            self.pan(-pan_distance, 0.0);
             pan_changed = true;
         }
         if ctx.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
-            self.center_x += pan_distance;
+            self.pan(pan_distance, 0.0);
             pan_changed = true;
         }
         if ctx.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-            self.center_y -= pan_distance;
+            self.pan(0.0, -pan_distance);
             pan_changed = true;
         }
         if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-            self.center_y += pan_distance;
+            self.pan(0.0, pan_distance);
             pan_changed = true;
         }
 
@@ -230,90 +622,135 @@ impl App for MandelbrotApp {
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            // Display the texture first
-            if self.texture.is_none() || self.needs_redraw {
-                let image = self.generate_fractal_image();
-                self.texture = Some(ctx.load_texture(
-                    "fractal_texture",
-                    image,
-                    egui::TextureOptions {
-                        minification: egui::TextureFilter::Linear,
-                        magnification: egui::TextureFilter::Linear,
-                        ..Default::default()
-                    },
-                ));
+            // Render at the panel's size in physical pixels, so the image stays sharp on HiDPI screens
+            let image_size = ui.available_size();
+            let pixels = image_size * ctx.pixels_per_point();
+            let render_size = [pixels.x.round().max(1.0) as usize, pixels.y.round().max(1.0) as usize];
+            if render_size != self.render_size {
+                self.render_size = render_size;
+                self.needs_redraw = true;
+            }
+
+            // Hand the current view over to the render thread, the previous
+            // texture stays on screen until the new frame arrives
+            if self.needs_redraw && self.zoom_animating {
+                // Come back once the animation is over
+                ctx.request_repaint();
+            } else if self.needs_redraw {
+                self.renderer.request(self.view(), self.render_threads);
                 self.needs_redraw = false;
             }
 
-            // Display the image and get response for interaction
-            let (response, image_rect) = if let Some(texture) = &self.texture {
-                let img_response = ui.add(egui::Image::from_texture(texture).sense(egui::Sense::click_and_drag()));
-                let rect = img_response.rect;
-                (img_response, rect)
-            } else {
-                let resp = ui.allocate_response(
-                    egui::vec2(WIDTH as f32, HEIGHT as f32),
-                    egui::Sense::click_and_drag()
-                );
-                let rect = resp.rect;
-                (resp, rect)
-            };
-
-            // Handle zoom with scroll wheel
-            if response.hovered() {
-                let scroll_delta = ctx.input(|i| i.smooth_scroll_delta.y);
-                if scroll_delta != 0.0 {
-                    let zoom_factor = if scroll_delta > 0.0 { 1.1 } else { 0.9 };
-                    self.zoom *= zoom_factor;
-                    self.needs_redraw = true;
+            if let Some(result) = self.renderer.latest() {
+                if result.complete {
+                    self.last_render_time = Some(result.elapsed);
+                    self.render_stats = result.stats;
+                }
+                match result.frame {
+                    RenderedFrame::Image(image) => {
+                        self.samples = None;
+                        self.samples_view = None;
+                        self.show_image(ctx, image, result.view);
+                    }
+                    RenderedFrame::Samples(samples) => {
+                        self.samples = Some(samples);
+                        self.samples_view = Some(result.view);
+                        self.needs_recolor = true;
+                    }
+                }
+            }
+
+            // Color changes and new samples both only need the cheap coloring pass
+            if self.needs_recolor {
+                self.needs_recolor = false;
+                // Keep the old frame up until one with everything the coloring needs arrives
+                if let Some(samples) = self.samples.clone()
+                    && let Some(view) = self.samples_view.clone()
+                    && samples.supports(&self.colors)
+                {
+                    self.show_image(ctx, samples.colorize(&self.colors), view);
+                }
+            }
+
+            // Display the image and get response for interaction. While zooming,
+            // panning or resizing the last frame is shown where it lies in the
+            // current view until the next one arrives.
+            let (image_rect, response) = ui.allocate_exact_size(image_size, egui::Sense::click_and_drag());
+            if let Some(texture) = &self.texture {
+                let rect = match &self.texture_view {
+                    Some(view) if view.fractal_type == self.fractal_type => self.frame_rect(view, image_rect),
+                    _ => image_rect,
+                };
+                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                ui.painter_at(image_rect).image(texture.id(), rect, uv, egui::Color32::WHITE);
+            }
+
+            // The trap marker sits on top of the image so it gets its drags first
+            if self.fractal_type != FractalType::Koch && self.colors.mode.uses_trap() {
+                self.orbit_trap_marker(ui, image_rect);
+            }
+
+            // Handle zoom with scroll wheel and pinch, toward the pointer
+            self.zoom_animating = false;
+            if response.hovered()
+                && let Some(pointer) = ctx.input(|i| i.pointer.hover_pos())
+            {
+                // egui spreads a wheel notch over a few frames in the smoothed delta
+                let (scroll_delta, pinch) = ctx.input(|i| {
+                    let scroll = if self.smooth_zoom { i.smooth_scroll_delta } else { i.raw_scroll_delta };
+                    (scroll.y, i.zoom_delta())
+                });
+                let zoom_factor = pinch as f64 * self.zoom_step.powf(scroll_delta as f64 / SCROLL_NOTCH);
+                if zoom_factor != 1.0 {
+                    self.zoom_at(zoom_factor, pointer, image_rect);
                 }
+                self.zoom_animating = self.smooth_zoom && scroll_delta != 0.0;
             }
 
             // Handle zoom rectangle selection (Shift + drag) or pan (normal drag)
             let shift_held = ctx.input(|i| i.modifiers.shift);
 
-            if response.drag_started() {
-                if let Some(mouse_pos) = ctx.input(|i| i.pointer.interact_pos()) {
-                    // Only process if mouse is within the image
-                    if image_rect.contains(mouse_pos) {
-                        if shift_held {
-                            // Start panning
-                            self.last_mouse_pos = Some(mouse_pos);
-                            self.dragging = true;
-                        } else {
-                            // Start zoom rectangle selection
-                            self.zoom_rect_start = Some(mouse_pos);
-                            self.selecting_zoom_rect = true;
-                        }
+            if response.drag_started()
+                && let Some(mouse_pos) = ctx.input(|i| i.pointer.interact_pos())
+            {
+                // Only process if mouse is within the image
+                if image_rect.contains(mouse_pos) {
+                    if shift_held {
+                        // Start panning
+                        self.last_mouse_pos = Some(mouse_pos);
+                        self.dragging = true;
+                    } else {
+                        // Start zoom rectangle selection
+                        self.zoom_rect_start = Some(mouse_pos);
+                        self.selecting_zoom_rect = true;
                     }
                 }
             }
 
-            if response.dragged() {
-                if let Some(mouse_pos) = ctx.input(|i| i.pointer.interact_pos()) {
-                    if self.selecting_zoom_rect && !shift_held {
-                        // Update zoom rectangle end point
-                        self.zoom_rect_end = Some(mouse_pos);
-                    } else if self.dragging && shift_held {
-                        // Handle panning
-                        if let Some(last_pos) = self.last_mouse_pos {
-                            let delta = mouse_pos - last_pos;
+            if response.dragged()
+                && let Some(mouse_pos) = ctx.input(|i| i.pointer.interact_pos())
+            {
+                if self.selecting_zoom_rect && !shift_held {
+                    // Update zoom rectangle end point
+                    self.zoom_rect_end = Some(mouse_pos);
+                } else if self.dragging && shift_held {
+                    // Handle panning
+                    if let Some(last_pos) = self.last_mouse_pos {
+                        let delta = mouse_pos - last_pos;
 
-                            // Convert pixel delta to complex plane delta
-                            let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
-                            let height_range = 3.0 / self.zoom;
-                            let width_range = height_range * aspect_ratio;
+                        // Convert pixel delta to complex plane delta
+                        let aspect_ratio = image_rect.aspect_ratio() as f64;
+                        let height_range = 3.0 / self.zoom;
+                        let width_range = height_range * aspect_ratio;
 
-                            let scale_x = width_range / WIDTH as f64;
-                            let scale_y = height_range / HEIGHT as f64;
+                        let scale_x = width_range / image_rect.width() as f64;
+                        let scale_y = height_range / image_rect.height() as f64;
 
-                            self.center_x -= delta.x as f64 * scale_x;
-                            self.center_y -= delta.y as f64 * scale_y;
+                        self.pan(-delta.x as f64 * scale_x, -delta.y as f64 * scale_y);
 
-                            self.needs_redraw = true;
-                        }
-                        self.last_mouse_pos = Some(mouse_pos);
+                        self.needs_redraw = true;
                     }
+                    self.last_mouse_pos = Some(mouse_pos);
                 }
             }
 
@@ -392,230 +829,167 @@ impl App for MandelbrotApp {
 }
 
 impl MandelbrotApp {
-    fn generate_fractal_image(&self) -> egui::ColorImage {
-        let mut image = egui::ColorImage::new([WIDTH as usize, HEIGHT as usize], egui::Color32::BLACK);
-
-        match self.fractal_type {
-            FractalType::Koch => {
-                self.generate_koch_curve(&mut image);
-            },
-            _ => {
-                // Calculate the bounds of the current view
-                let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
-                let height_range = 3.0 / self.zoom;
-                let width_range = height_range * aspect_ratio;
-
-                let left = self.center_x - width_range / 2.0;
-                let right = self.center_x + width_range / 2.0;
-                let top = self.center_y - height_range / 2.0;
-                let bottom = self.center_y + height_range / 2.0;
-
-                for y in 0..HEIGHT {
-                    for x in 0..WIDTH {
-                        // Map pixel coordinates to complex plane based on current view
-                        let px = left + (x as f64 / WIDTH as f64) * (right - left);
-                        let py = top + (y as f64 / HEIGHT as f64) * (bottom - top);
-
-                        let (mut zx, mut zy, cx, cy) = match self.fractal_type {
-                            FractalType::Mandelbrot => {
-                                // Mandelbrot: z starts at 0, c is the pixel coordinate
-                                (0.0, 0.0, px, py)
-                            },
-                            FractalType::Julia => {
-                                // Julia: z starts at pixel coordinate, c is fixed
-                                (px, py, self.julia_c_real, self.julia_c_imag)
-                            },
-                            _ => unreachable!(),
-                        };
-
-                        // Increase max iterations for higher zoom levels to maintain detail
-                        let max_iter = (255.0 + (self.zoom.log10() * 100.0).max(0.0)) as i32;
-                        let max_iter = max_iter.min(1000); // Cap at 1000 for performance
-
-                        let mut iter = 0;
-                        while zx * zx + zy * zy < 4.0 && iter < max_iter {
-                            let xtemp = zx * zx - zy * zy + cx;
-                            zy = 2.0 * zx * zy + cy;
-                            zx = xtemp;
-                            iter += 1;
-                        }
-
-                        let color = if iter == max_iter {
-                            egui::Color32::BLACK
-                        } else {
-                            // High-contrast color schemes for better visibility
-                            match self.fractal_type {
-                                FractalType::Mandelbrot => {
-                                    // Hot color palette with green: black -> red -> yellow -> green -> cyan -> white
-                                    let t = iter as f64 / max_iter as f64;
-                                    let t = t.powf(0.5); // Apply gamma correction for better distribution
-
-                                    if t < 0.2 {
-                                        // Black to red
-                                        let intensity = (t * 5.0 * 255.0) as u8;
-                                        egui::Color32::from_rgb(intensity, 0, 0)
-                                    } else if t < 0.4 {
-                                        // Red to yellow
-                                        let intensity = ((t - 0.2) * 5.0 * 255.0) as u8;
-                                        egui::Color32::from_rgb(255, intensity, 0)
-                                    } else if t < 0.6 {
-                                        // Yellow to green
-                                        let intensity = ((t - 0.4) * 5.0 * 255.0) as u8;
-                                        egui::Color32::from_rgb(255 - intensity, 255, 0)
-                                    } else if t < 0.8 {
-                                        // Green to cyan
-                                        let intensity = ((t - 0.6) * 5.0 * 255.0) as u8;
-                                        egui::Color32::from_rgb(0, 255, intensity)
-                                    } else {
-                                        // Cyan to white
-                                        let intensity = ((t - 0.8) * 5.0 * 255.0) as u8;
-                                        egui::Color32::from_rgb(intensity, 255, 255)
-                                    }
-                                },
-                                FractalType::Julia => {
-                                    // Rainbow palette with high contrast
-                                    let t = iter as f64 / max_iter as f64;
-                                    let t = t.powf(0.7); // Gamma correction
-                                    let hue = t * 6.0; // 6 color segments
-
-                                    match hue as i32 {
-                                        0 => {
-                                            // Red to Orange
-                                            let f = hue.fract();
-                                            egui::Color32::from_rgb(255, (f * 165.0) as u8, 0)
-                                        },
-                                        1 => {
-                                            // Orange to Yellow
-                                            let f = hue.fract();
-                                            egui::Color32::from_rgb(255, (165.0 + f * 90.0) as u8, 0)
-                                        },
-                                        2 => {
-                                            // Yellow to Green
-                                            let f = hue.fract();
-                                            egui::Color32::from_rgb((255.0 * (1.0 - f)) as u8, 255, 0)
-                                        },
-                                        3 => {
-                                            // Green to Cyan
-                                            let f = hue.fract();
-                                            egui::Color32::from_rgb(0, 255, (f * 255.0) as u8)
-                                        },
-                                        4 => {
-                                            // Cyan to Blue
-                                            let f = hue.fract();
-                                            egui::Color32::from_rgb(0, (255.0 * (1.0 - f)) as u8, 255)
-                                        },
-                                        _ => {
-                                            // Blue to Magenta
-                                            let f = hue.fract();
-                                            egui::Color32::from_rgb((f * 255.0) as u8, 0, 255)
-                                        }
-                                    }
-                                },
-                                _ => unreachable!(),
-                            }
-                        };
-                        let index = y as usize * image.width() + x as usize;
-                        image.pixels[index] = color;
-                    }
-                }
-            }
+    fn view(&self) -> FractalView {
+        // The render derives its working precision from the center, make sure it covers the zoom
+        let frac_bits = BigFixed::precision_for_zoom(self.zoom).max(self.center_x.frac_bits());
+        FractalView {
+            width: self.render_size[0],
+            height: self.render_size[1],
+            center_x: self.center_x.with_precision(frac_bits),
+            center_y: self.center_y.with_precision(frac_bits),
+            zoom: self.zoom,
+            fractal_type: self.fractal_type,
+            julia_c_real: self.julia_c_real,
+            julia_c_imag: self.julia_c_imag,
+            series_approximation: self.series_approximation,
+            distance_estimation: self.colors.needs_derivative(),
+            orbit_trap: self.colors.mode.uses_trap().then_some(self.orbit_trap),
+            interior_analysis: self.colors.interior.needs_analysis(),
+            interior_coloring: self.colors.interior != InteriorMode::Black,
+            strategy: self.render_strategy,
+            fill_guard: self.fill_guard,
+            max_iter_policy: self.max_iter_policy,
+            supersampling: self.supersampling,
+            colors: self.colors.clone(),
         }
-        image
     }
 
-    fn generate_koch_curve(&self, image: &mut egui::ColorImage) {
-        // Generate Koch snowflake with iteration depth based on zoom level
-        let iterations = ((self.zoom.log2() + 1.0).max(0.0) as usize).min(5);
+    /// Draw the orbit trap's center on the image and let it be dragged around.
+    fn orbit_trap_marker(&mut self, ui: &mut egui::Ui, image_rect: egui::Rect) {
+        let height_range = 3.0 / self.zoom;
+        let width_range = height_range * image_rect.aspect_ratio() as f64;
+        let (center_x, center_y) = (self.center_x.to_f64(), self.center_y.to_f64());
+        let (trap_x, trap_y) = self.orbit_trap.center;
+        let pos = egui::pos2(
+            image_rect.left() + (((trap_x - center_x) / width_range + 0.5) * image_rect.width() as f64) as f32,
+            image_rect.top() + (((trap_y - center_y) / height_range + 0.5) * image_rect.height() as f64) as f32,
+        );
+        if !image_rect.contains(pos) {
+            return;
+        }
+
+        let response = ui.interact(
+            egui::Rect::from_center_size(pos, egui::vec2(16.0, 16.0)),
+            ui.id().with("orbit_trap_marker"),
+            egui::Sense::drag(),
+        );
+        if response.dragged() {
+            let delta = response.drag_delta();
+            self.orbit_trap.center.0 += delta.x as f64 / image_rect.width() as f64 * width_range;
+            self.orbit_trap.center.1 += delta.y as f64 / image_rect.height() as f64 * height_range;
+            self.needs_redraw = true;
+        }
+
+        let color = if response.hovered() || response.dragged() {
+            egui::Color32::YELLOW
+        } else {
+            egui::Color32::WHITE
+        };
+        let stroke = egui::Stroke::new(2.0, color);
+        ui.painter().circle_stroke(pos, 6.0, stroke);
+        ui.painter().line_segment([pos - egui::vec2(10.0, 0.0), pos + egui::vec2(10.0, 0.0)], stroke);
+        ui.painter().line_segment([pos - egui::vec2(0.0, 10.0), pos + egui::vec2(0.0, 10.0)], stroke);
+    }
 
-        // Create initial horizontal line segment centered at current view
-        let size = 2.0 / self.zoom;
+    /// Where a frame rendered for `view` lies on screen, with the current view filling `image_rect`.
+    fn frame_rect(&self, view: &FractalView, image_rect: egui::Rect) -> egui::Rect {
+        // Complex plane units per point of the current view
+        let scale = 3.0 / self.zoom / image_rect.height() as f64;
+        let height = 3.0 / view.zoom;
+        let width = height * view.width as f64 / view.height as f64;
+        let dx = (&view.center_x - &self.center_x).to_f64();
+        let dy = (&view.center_y - &self.center_y).to_f64();
+        egui::Rect::from_center_size(
+            image_rect.center() + egui::vec2((dx / scale) as f32, (dy / scale) as f32),
+            egui::vec2((width / scale) as f32, (height / scale) as f32),
+        )
+    }
 
-        let p1 = (self.center_x - size / 2.0, self.center_y);
-        let p2 = (self.center_x + size / 2.0, self.center_y);
+    fn show_image(&mut self, ctx: &egui::Context, image: egui::ColorImage, view: FractalView) {
+        self.texture_view = Some(view);
+        let options = egui::TextureOptions {
+            minification: egui::TextureFilter::Linear,
+            magnification: egui::TextureFilter::Linear,
+            ..Default::default()
+        };
+        match &mut self.texture {
+            Some(texture) => texture.set(image, options),
+            None => self.texture = Some(ctx.load_texture("fractal_texture", image, options)),
+        }
+    }
 
-        // Generate Koch curve for a single line
-        let mut segments = Vec::new();
-        self.generate_koch_segments(p1, p2, iterations, &mut segments);
+    /// Go to a location read back from an exported image.
+    fn open_location(&mut self, location: Location) {
+        self.fractal_type = location.fractal_type;
+        self.center_x = location.center_x;
+        self.center_y = location.center_y;
+        self.zoom = location.zoom;
+        (self.julia_c_real, self.julia_c_imag) = location.julia_c;
+        // The image was rendered with this exact limit, whatever the policy was
+        self.max_iter_policy.mode = MaxIterMode::Fixed;
+        self.max_iter_policy.fixed = location.max_iter.max(1);
+        self.colors.palette = location.palette;
+        self.needs_redraw = true;
+        self.needs_recolor = true;
+    }
 
-        // Draw the segments
-        for (start, end) in segments {
-            self.draw_line(image, start, end);
+    /// The current view, iteration and coloring settings, as saved to a location file.
+    fn session(&self) -> Session {
+        // The limit of the last render, unless there hasn't been one yet
+        let max_iter = Some(self.render_stats.max_iter).filter(|&max_iter| max_iter > 0).unwrap_or(self.max_iter_policy.fixed);
+        Session {
+            location: Location::of(&self.view(), max_iter, &self.colors.palette),
+            max_iter_policy: self.max_iter_policy,
+            colors: self.colors.clone(),
+            orbit_trap: self.orbit_trap,
         }
     }
 
-    fn generate_koch_segments(&self, start: (f64, f64), end: (f64, f64), depth: usize, segments: &mut Vec<((f64, f64), (f64, f64))>) {
-        if depth == 0 {
-            segments.push((start, end));
-            return;
+    /// Open the location file at `session_path`, reporting failure in the side panel.
+    fn open_session_file(&mut self) {
+        match Session::load(Path::new(&self.session_path)) {
+            Ok(session) => {
+                self.open_location(session.location);
+                // Back to the policy the limit came from and the rest of the coloring
+                self.max_iter_policy = session.max_iter_policy;
+                self.colors = session.colors;
+                self.orbit_trap = session.orbit_trap;
+                self.session_message = None;
+            }
+            Err(err) => self.session_message = Some(err),
         }
+    }
 
-        // Calculate the four points for Koch curve iteration
-        let dx = end.0 - start.0;
-        let dy = end.1 - start.1;
-
-        // Four key points along the line
-        let p1 = start;
-        let p2 = (start.0 + dx / 3.0, start.1 + dy / 3.0);
-        let p4 = (start.0 + 2.0 * dx / 3.0, start.1 + 2.0 * dy / 3.0);
-        let p5 = end;
-
-        // Calculate the peak point (equilateral triangle bump)
-        let mid_x = (p2.0 + p4.0) / 2.0;
-        let mid_y = (p2.1 + p4.1) / 2.0;
-        let segment_length = (dx * dx + dy * dy).sqrt() / 3.0;
-        let height = segment_length * (3.0_f64.sqrt() / 2.0);
-
-        // Create the bump perpendicular to the line
-        let normal_x = -dy / (dx * dx + dy * dy).sqrt();
-        let normal_y = dx / (dx * dx + dy * dy).sqrt();
-        let p3 = (mid_x + normal_x * height, mid_y + normal_y * height);
-
-        // Recursively generate segments
-        self.generate_koch_segments(p1, p2, depth - 1, segments);
-        self.generate_koch_segments(p2, p3, depth - 1, segments);
-        self.generate_koch_segments(p3, p4, depth - 1, segments);
-        self.generate_koch_segments(p4, p5, depth - 1, segments);
+    fn set_view(&mut self, center_x: f64, center_y: f64, zoom: f64) {
+        let frac_bits = BigFixed::precision_for_zoom(zoom);
+        self.center_x = BigFixed::from_f64(center_x, frac_bits);
+        self.center_y = BigFixed::from_f64(center_y, frac_bits);
+        self.zoom = zoom;
     }
 
-    fn draw_line(&self, image: &mut egui::ColorImage, start: (f64, f64), end: (f64, f64)) {
-        // Convert world coordinates to screen coordinates
-        let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
+    /// Move the view center by an offset in the complex plane.
+    fn pan(&mut self, dx: f64, dy: f64) {
+        // Grow the center precision along with the zoom so the offset isn't rounded away
+        let frac_bits = BigFixed::precision_for_zoom(self.zoom)
+            .max(self.center_x.frac_bits());
+        self.center_x = self.center_x.with_precision(frac_bits).add_f64(dx);
+        self.center_y = self.center_y.with_precision(frac_bits).add_f64(dy);
+    }
+
+    /// Zoom by `factor`, keeping the point of the plane under `pointer` in place.
+    fn zoom_at(&mut self, factor: f64, pointer: egui::Pos2, image_rect: egui::Rect) {
+        let aspect_ratio = image_rect.aspect_ratio() as f64;
         let height_range = 3.0 / self.zoom;
         let width_range = height_range * aspect_ratio;
 
-        let left = self.center_x - width_range / 2.0;
-        let right = self.center_x + width_range / 2.0;
-        let top = self.center_y - height_range / 2.0;
-        let bottom = self.center_y + height_range / 2.0;
-
-        let sx = ((start.0 - left) / (right - left) * WIDTH as f64) as i32;
-        let sy = ((start.1 - top) / (bottom - top) * HEIGHT as f64) as i32;
-        let ex = ((end.0 - left) / (right - left) * WIDTH as f64) as i32;
-        let ey = ((end.1 - top) / (bottom - top) * HEIGHT as f64) as i32;
-
-        // Simple line drawing with thick lines for better visibility
-        let dx = (ex - sx).abs();
-        let dy = (ey - sy).abs();
-        let steps = dx.max(dy).max(1);
-
-        for i in 0..=steps {
-            let t = i as f64 / steps as f64;
-            let x = (sx as f64 + t * (ex - sx) as f64) as i32;
-            let y = (sy as f64 + t * (ey - sy) as f64) as i32;
-
-            // Draw thick line (3x3 pixels)
-            for dy in -1..=1 {
-                for dx in -1..=1 {
-                    let px = x + dx;
-                    let py = y + dy;
-                    if px >= 0 && px < WIDTH as i32 && py >= 0 && py < HEIGHT as i32 {
-                        let index = py as usize * image.width() + px as usize;
-                        // Use bright green color for Koch curve
-                        image.pixels[index] = egui::Color32::from_rgb(0, 255, 0);
-                    }
-                }
-            }
-        }
+        // Offset of the pointer from the center, shrinks along with the view
+        let offset_x = ((pointer.x - image_rect.left()) / image_rect.width() - 0.5) as f64 * width_range;
+        let offset_y = ((pointer.y - image_rect.top()) / image_rect.height() - 0.5) as f64 * height_range;
+        // Past the deepest zoom the pointer stays put, the view just stops zooming
+        let factor = (self.zoom * factor).min(MAX_ZOOM) / self.zoom;
+        self.zoom *= factor;
+        self.pan(offset_x * (1.0 - 1.0 / factor), offset_y * (1.0 - 1.0 / factor));
+        self.needs_redraw = true;
     }
 
     fn zoom_to_rectangle(&mut self, start: egui::Pos2, end: egui::Pos2, image_rect: egui::Rect) {
@@ -644,25 +1018,26 @@ impl MandelbrotApp {
         let rel_end_x = rel_end_x.clamp(0.0, 1.0);
         let rel_end_y = rel_end_y.clamp(0.0, 1.0);
 
-        // Calculate current view bounds in complex plane
-        let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
+        // Calculate current view bounds in complex plane, relative to the center
+        // so the math stays in f64 no matter how deep the zoom is
+        let aspect_ratio = image_rect.aspect_ratio() as f64;
         let height_range = 3.0 / self.zoom;
         let width_range = height_range * aspect_ratio;
 
-        let current_left = self.center_x - width_range / 2.0;
-        let current_right = self.center_x + width_range / 2.0;
-        let current_top = self.center_y - height_range / 2.0;
-        let current_bottom = self.center_y + height_range / 2.0;
+        let current_left = -width_range / 2.0;
+        let current_right = width_range / 2.0;
+        let current_top = -height_range / 2.0;
+        let current_bottom = height_range / 2.0;
 
-        // Map relative coordinates to complex plane coordinates
+        // Map relative coordinates to complex plane offsets
         let selected_left = current_left + rel_start_x as f64 * (current_right - current_left);
         let selected_right = current_left + rel_end_x as f64 * (current_right - current_left);
         let selected_top = current_top + rel_start_y as f64 * (current_bottom - current_top);
         let selected_bottom = current_top + rel_end_y as f64 * (current_bottom - current_top);
 
-        // Calculate new center
-        let new_center_x = (selected_left + selected_right) / 2.0;
-        let new_center_y = (selected_top + selected_bottom) / 2.0;
+        // Calculate offset of the new center
+        let center_offset_x = (selected_left + selected_right) / 2.0;
+        let center_offset_y = (selected_top + selected_bottom) / 2.0;
 
         // Calculate how much we need to zoom to fit the selected rectangle
         let selected_width = selected_right - selected_left;
@@ -676,9 +1051,8 @@ impl MandelbrotApp {
         // Apply the zoom (only if it would zoom in)
 
         if zoom_factor > 1.0 {
-            self.center_x = new_center_x;
-            self.center_y = new_center_y;
-            self.zoom *= zoom_factor;
+            self.zoom = (self.zoom * zoom_factor).min(MAX_ZOOM);
+            self.pan(center_offset_x, center_offset_y);
             self.needs_redraw = true;
         } else {
             // Show visual feedback for invalid rectangle selection
@@ -688,14 +1062,23 @@ impl MandelbrotApp {
 
 fn main() {
     env_logger::init();
+    // A location file to start at, as saved from the side panel
+    let location_file = std::env::args().nth(1);
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size(egui::vec2(WIDTH as f32 + 250.0, HEIGHT as f32)), // Extra width for side panel
+            .with_inner_size(egui::vec2(1200.0 + 250.0, 800.0)), // Extra width for side panel
         ..Default::default()
     };
     let _ = eframe::run_native(
         "Fractal Explorer",
         options,
-        Box::new(|_cc| Box::new(MandelbrotApp::default())),
+        Box::new(|cc| {
+            let mut app = MandelbrotApp::new(cc);
+            if let Some(path) = location_file {
+                app.session_path = path;
+                app.open_session_file();
+            }
+            Box::new(app)
+        }),
     );
 }
@@ -0,0 +1,85 @@
+/// Shape of an orbit trap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrapShape {
+    Point,
+    /// Line through the trap center at the trap's angle.
+    Line,
+    /// Two perpendicular lines crossing at the trap center.
+    Cross,
+    /// Circle of the trap's radius around its center.
+    Circle,
+}
+
+impl TrapShape {
+    pub const ALL: [TrapShape; 4] = [TrapShape::Point, TrapShape::Line, TrapShape::Cross, TrapShape::Circle];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrapShape::Point => "Point",
+            TrapShape::Line => "Line",
+            TrapShape::Cross => "Cross",
+            TrapShape::Circle => "Circle",
+        }
+    }
+}
+
+/// A shape in the z plane that catches orbits passing close to it.
+///
+/// Every point of an orbit is checked against the trap, recording the closest
+/// approach and the first iteration that came within `size` of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitTrap {
+    pub shape: TrapShape,
+    pub center: (f64, f64),
+    /// Direction of the line shapes, in degrees.
+    pub angle: f64,
+    /// Radius of the circle shape.
+    pub radius: f64,
+    /// Distance within which the trap counts as hit, also the distance the
+    /// trap coloring fades out at.
+    pub size: f64,
+}
+
+impl Default for OrbitTrap {
+    fn default() -> Self {
+        Self { shape: TrapShape::Point, center: (0.0, 0.0), angle: 0.0, radius: 0.5, size: 0.1 }
+    }
+}
+
+impl OrbitTrap {
+    /// Distance of `z` from the trap shape.
+    pub fn distance(&self, z: (f64, f64)) -> f64 {
+        let (x, y) = (z.0 - self.center.0, z.1 - self.center.1);
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        match self.shape {
+            TrapShape::Point => x.hypot(y),
+            TrapShape::Line => (x * sin - y * cos).abs(),
+            TrapShape::Cross => (x * sin - y * cos).abs().min((x * cos + y * sin).abs()),
+            TrapShape::Circle => (x.hypot(y) - self.radius).abs(),
+        }
+    }
+}
+
+/// What a trap caught of one orbit so far.
+#[derive(Debug, Clone, Copy)]
+pub struct TrapState {
+    pub min_distance: f64,
+    /// First iteration within the trap's size, if any.
+    pub hit: Option<i32>,
+}
+
+impl Default for TrapState {
+    fn default() -> Self {
+        Self { min_distance: f64::INFINITY, hit: None }
+    }
+}
+
+impl TrapState {
+    pub fn visit(&mut self, trap: &OrbitTrap, z: (f64, f64), iter: i32) {
+        let distance = trap.distance(z);
+        self.min_distance = self.min_distance.min(distance);
+        if self.hit.is_none() && distance < trap.size {
+            self.hit = Some(iter);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use eframe::egui::Color32;
+
+/// How colors between two stops are blended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Smoothstep easing, hides the kinks at the stops.
+    Smooth,
+    /// No blending, every stop's color holds until the next stop.
+    Step,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 3] = [Interpolation::Linear, Interpolation::Smooth, Interpolation::Step];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interpolation::Linear => "Linear",
+            Interpolation::Smooth => "Smooth",
+            Interpolation::Step => "Step",
+        }
+    }
+}
+
+/// What happens to positions outside the 0..1 range of the gradient.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepeatMode {
+    /// Stick to the first/last color.
+    Clamp,
+    /// Start over from the first color.
+    Repeat,
+    /// Run the gradient backwards every other cycle.
+    Mirror,
+}
+
+impl RepeatMode {
+    pub const ALL: [RepeatMode; 3] = [RepeatMode::Clamp, RepeatMode::Repeat, RepeatMode::Mirror];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepeatMode::Clamp => "Clamp",
+            RepeatMode::Repeat => "Repeat",
+            RepeatMode::Mirror => "Mirror",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStop {
+    /// Position along the gradient, 0..=1.
+    pub position: f32,
+    pub color: Color32,
+}
+
+impl ColorStop {
+    pub fn new(position: f32, r: u8, g: u8, b: u8) -> Self {
+        Self { position, color: Color32::from_rgb(r, g, b) }
+    }
+
+    /// The stop as its position and `rrggbbaa` hex color, `0.5 ff8000ff`.
+    fn to_text(self) -> String {
+        let [r, g, b, a] = self.color.to_srgba_unmultiplied();
+        format!("{} {r:02x}{g:02x}{b:02x}{a:02x}", self.position)
+    }
+
+    /// Inverse of `to_text`.
+    fn parse(text: &str) -> Option<Self> {
+        let (position, hex) = text.trim().split_once(' ')?;
+        let rgba = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 8)?;
+        let [r, g, b, a] = rgba.to_be_bytes();
+        Some(Self { position: position.parse().ok()?, color: Color32::from_rgba_unmultiplied(r, g, b, a) })
+    }
+}
+
+/// Gradient that maps a normalized value to a color.
+///
+/// The value is first transformed by `scale` and `offset`, folded back into
+/// 0..1 according to `repeat` and then looked up between the color stops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    /// Sorted by position.
+    pub stops: Vec<ColorStop>,
+    pub interpolation: Interpolation,
+    pub repeat: RepeatMode,
+    pub offset: f32,
+    pub scale: f32,
+}
+
+impl Palette {
+    fn from_stops(name: &str, stops: Vec<ColorStop>) -> Self {
+        Self {
+            name: name.to_owned(),
+            stops,
+            interpolation: Interpolation::Linear,
+            repeat: RepeatMode::Clamp,
+            offset: 0.0,
+            scale: 1.0,
+        }
+    }
+
+    /// Black -> red -> yellow -> green -> cyan -> white, the original Mandelbrot colors.
+    pub fn hot() -> Self {
+        Self::from_stops("Hot", vec![
+            ColorStop::new(0.0, 0, 0, 0),
+            ColorStop::new(0.2, 255, 0, 0),
+            ColorStop::new(0.4, 255, 255, 0),
+            ColorStop::new(0.6, 0, 255, 0),
+            ColorStop::new(0.8, 0, 255, 255),
+            ColorStop::new(1.0, 255, 255, 255),
+        ])
+    }
+
+    /// Red through the hues to magenta, the original Julia colors.
+    pub fn rainbow() -> Self {
+        Self::from_stops("Rainbow", vec![
+            ColorStop::new(0.0, 255, 0, 0),
+            ColorStop::new(1.0 / 6.0, 255, 165, 0),
+            ColorStop::new(2.0 / 6.0, 255, 255, 0),
+            ColorStop::new(3.0 / 6.0, 0, 255, 0),
+            ColorStop::new(4.0 / 6.0, 0, 255, 255),
+            ColorStop::new(5.0 / 6.0, 0, 0, 255),
+            ColorStop::new(1.0, 255, 0, 255),
+        ])
+    }
+
+    pub fn grayscale() -> Self {
+        Self::from_stops("Grayscale", vec![
+            ColorStop::new(0.0, 0, 0, 0),
+            ColorStop::new(1.0, 255, 255, 255),
+        ])
+    }
+
+    pub fn ocean() -> Self {
+        Self::from_stops("Ocean", vec![
+            ColorStop::new(0.0, 0, 7, 100),
+            ColorStop::new(0.16, 32, 107, 203),
+            ColorStop::new(0.42, 237, 255, 255),
+            ColorStop::new(0.6425, 255, 170, 0),
+            ColorStop::new(0.8575, 0, 2, 0),
+            ColorStop::new(1.0, 0, 7, 100),
+        ])
+    }
+
+    pub fn twilight() -> Self {
+        Self::from_stops("Twilight", vec![
+            ColorStop::new(0.0, 20, 10, 40),
+            ColorStop::new(0.3, 120, 40, 120),
+            ColorStop::new(0.55, 240, 120, 80),
+            ColorStop::new(0.8, 250, 220, 150),
+            ColorStop::new(1.0, 255, 255, 240),
+        ])
+    }
+
+    pub fn presets() -> Vec<Palette> {
+        vec![Self::hot(), Self::rainbow(), Self::grayscale(), Self::ocean(), Self::twilight()]
+    }
+
+    /// The stops as comma separated `position rrggbbaa` pairs, the form
+    /// location files and exported images save them in.
+    pub fn stops_text(&self) -> String {
+        self.stops.iter().map(|stop| stop.to_text()).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Inverse of `stops_text`, `None` unless there are at least two stops
+    /// and they are sorted by position.
+    pub fn parse_stops(text: &str) -> Option<Vec<ColorStop>> {
+        text.split(',')
+            .map(ColorStop::parse)
+            .collect::<Option<Vec<_>>>()
+            .filter(|stops| stops.len() >= 2 && stops.is_sorted_by(|a, b| a.position <= b.position))
+    }
+
+    /// Color for a normalized value, nominally in 0..=1.
+    pub fn color_at(&self, t: f32) -> Color32 {
+        let u = t * self.scale + self.offset;
+        let u = match self.repeat {
+            RepeatMode::Clamp => u.clamp(0.0, 1.0),
+            RepeatMode::Repeat => u.rem_euclid(1.0),
+            RepeatMode::Mirror => {
+                let u = u.rem_euclid(2.0);
+                if u > 1.0 { 2.0 - u } else { u }
+            }
+        };
+        self.gradient_at(u)
+    }
+
+    /// Color of the raw gradient at position `u` in 0..=1, ignoring scale, offset and repeat.
+    pub fn gradient_at(&self, u: f32) -> Color32 {
+        let (Some(first), Some(last)) = (self.stops.first(), self.stops.last()) else {
+            return Color32::BLACK;
+        };
+        if u <= first.position {
+            return first.color;
+        }
+        if u >= last.position {
+            return last.color;
+        }
+
+        // Find the pair of stops around u
+        let i = self.stops.iter().position(|stop| stop.position > u).unwrap_or(self.stops.len() - 1);
+        let (a, b) = (self.stops[i - 1], self.stops[i]);
+        let span = b.position - a.position;
+        let f = if span > 0.0 { (u - a.position) / span } else { 1.0 };
+        let f = match self.interpolation {
+            Interpolation::Linear => f,
+            Interpolation::Smooth => f * f * (3.0 - 2.0 * f),
+            Interpolation::Step => 0.0,
+        };
+
+        let mix = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * f).round() as u8;
+        Color32::from_rgb(mix(a.color.r(), b.color.r()), mix(a.color.g(), b.color.g()), mix(a.color.b(), b.color.b()))
+    }
+}
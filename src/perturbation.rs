@@ -0,0 +1,310 @@
+use num::complex::Complex64;
+
+use crate::bigfixed::BigFixed;
+use crate::fractal::{escape_time_f64, CancelToken, FractalType, OrbitTracking, Sample, BAILOUT_SQ};
+
+/// Pauldelbrot's criterion: once |Z + δ| drops below this fraction of |Z| the
+/// delta has lost its precision against the reference and the pixel is glitched.
+const GLITCH_TOLERANCE: f64 = 1e-6;
+
+/// Largest relative error of the series against a directly iterated probe
+/// point before the approximation is considered invalid.
+const SERIES_TOLERANCE: f64 = 1e-9;
+
+/// Reference iterations between checks whether the render was cancelled.
+/// High precision iterations are slow, a long orbit takes seconds at deep zoom.
+const CANCEL_CHECK_INTERVAL: usize = 4096;
+
+/// Truncated power series `δ_n ≈ A·u + B·u² + C·u³` of the pixel delta after
+/// `skip` iterations, `u` being the pixel's initial offset from the reference.
+///
+/// All pixels share the coefficients, so the first `skip` iterations are
+/// evaluated once per view instead of once per pixel.
+#[derive(Debug, Clone)]
+pub struct SeriesApproximation {
+    pub skip: usize,
+    coefficients: [Complex64; 3],
+}
+
+impl SeriesApproximation {
+    fn evaluate(&self, u: Complex64) -> Complex64 {
+        let [a, b, c] = self.coefficients;
+        ((c * u + b) * u + a) * u
+    }
+
+    /// dδ/du, which is also the derivative of the full orbit after `skip` iterations.
+    fn derivative(&self, u: Complex64) -> Complex64 {
+        let [a, b, c] = self.coefficients;
+        (c * u * 3.0 + b * 2.0) * u + a
+    }
+}
+
+/// Result of iterating one pixel relative to a reference orbit.
+pub struct PerturbedPixel {
+    pub sample: Sample,
+    /// Set when the pixel needs another reference orbit to be trusted.
+    pub glitched: bool,
+}
+
+/// Orbit of a single reference point computed in arbitrary precision.
+///
+/// Pixels near the reference only iterate their small difference δ from it in
+/// f64: with `z = Z + δ` the Mandelbrot recurrence becomes
+/// `δ' = 2Zδ + δ² + δc`, which stays accurate long after `z` itself would
+/// have run out of f64 digits.
+pub struct ReferenceOrbit {
+    fractal_type: FractalType,
+    /// The reference point's c rounded to f64.
+    c: (f64, f64),
+    /// Offset of the reference point from the view center.
+    offset: (f64, f64),
+    /// Z_0 ..= Z_n rounded to f64, ends early if the reference escapes.
+    orbit: Vec<(f64, f64)>,
+    /// Z_n and c in full precision to continue the orbit from, `None` once it escaped.
+    state: Option<[BigFixed; 4]>,
+}
+
+impl ReferenceOrbit {
+    pub fn compute(
+        fractal_type: FractalType,
+        center: (&BigFixed, &BigFixed),
+        offset: (f64, f64),
+        julia_c: (f64, f64),
+        max_iter: i32,
+        cancel: &CancelToken,
+    ) -> Option<Self> {
+        let ref_x = center.0.add_f64(offset.0);
+        let ref_y = center.1.add_f64(offset.1);
+        let frac_bits = ref_x.frac_bits();
+        let c = match fractal_type {
+            FractalType::Mandelbrot => (ref_x.to_f64(), ref_y.to_f64()),
+            _ => julia_c,
+        };
+
+        let (zx, zy, cx, cy) = match fractal_type {
+            FractalType::Mandelbrot => (BigFixed::from_f64(0.0, frac_bits), BigFixed::from_f64(0.0, frac_bits), ref_x, ref_y),
+            FractalType::Julia => (
+                ref_x,
+                ref_y,
+                BigFixed::from_f64(julia_c.0, frac_bits),
+                BigFixed::from_f64(julia_c.1, frac_bits),
+            ),
+            _ => unreachable!(),
+        };
+
+        // Grown as it goes, most references escape long before the limit
+        let orbit = vec![(zx.to_f64(), zy.to_f64())];
+        let mut reference = Self { fractal_type, c, offset, orbit, state: Some([zx, zy, cx, cy]) };
+        reference.extend(max_iter, cancel).then_some(reference)
+    }
+
+    /// Continue the orbit up to `max_iter` iterations, unless it escaped before.
+    /// Returns false if the render was cancelled on the way.
+    pub fn extend(&mut self, max_iter: i32, cancel: &CancelToken) -> bool {
+        let Some([mut zx, mut zy, cx, cy]) = self.state.take() else {
+            return true;
+        };
+        while self.orbit.len() <= max_iter.max(0) as usize {
+            if self.orbit.len().is_multiple_of(CANCEL_CHECK_INTERVAL) && cancel.is_cancelled() {
+                return false;
+            }
+            let zx2 = zx.square();
+            let zy2 = zy.square();
+            let xy = &zx * &zy;
+            zy = &(&xy + &xy) + &cy;
+            zx = &(&zx2 - &zy2) + &cx;
+
+            let z = (zx.to_f64(), zy.to_f64());
+            self.orbit.push(z);
+            if z.0 * z.0 + z.1 * z.1 >= 4.0 {
+                return true;
+            }
+        }
+        self.state = Some([zx, zy, cx, cy]);
+        true
+    }
+
+    /// Build the series approximation for this reference and find how many
+    /// iterations it can skip while staying accurate at every probe point.
+    ///
+    /// Probes should be spread over the extremes of the view (corners, edges),
+    /// they are iterated exactly alongside the series and the first iteration
+    /// where any of them disagrees with it ends the skip.
+    pub fn series_approximation(&self, probes: &[(f64, f64)], max_iter: i32) -> SeriesApproximation {
+        let zero = Complex64::new(0.0, 0.0);
+        let one = Complex64::new(1.0, 0.0);
+        let mandelbrot = self.fractal_type == FractalType::Mandelbrot;
+
+        // Mandelbrot deltas start at 0 and gain δc every step, Julia deltas start at u
+        let mut approximation = SeriesApproximation {
+            skip: 0,
+            coefficients: [if mandelbrot { zero } else { one }, zero, zero],
+        };
+        let offsets: Vec<Complex64> = probes
+            .iter()
+            .map(|p| Complex64::new(p.0 - self.offset.0, p.1 - self.offset.1))
+            .collect();
+        let mut deltas: Vec<Complex64> = offsets.iter().map(|&u| if mandelbrot { zero } else { u }).collect();
+
+        let limit = (self.orbit.len() - 1).min(max_iter.max(0) as usize).saturating_sub(1);
+        for n in 0..limit {
+            let z = Complex64::new(self.orbit[n].0, self.orbit[n].1);
+            let [a, b, c] = approximation.coefficients;
+            let next = SeriesApproximation {
+                skip: n + 1,
+                coefficients: [
+                    z * a * 2.0 + if mandelbrot { one } else { zero },
+                    z * b * 2.0 + a * a,
+                    z * c * 2.0 + a * b * 2.0,
+                ],
+            };
+
+            let z_next = Complex64::new(self.orbit[n + 1].0, self.orbit[n + 1].1);
+            let mut valid = true;
+            for (delta, &u) in deltas.iter_mut().zip(&offsets) {
+                *delta = z * *delta * 2.0 + *delta * *delta + if mandelbrot { u } else { zero };
+                let full = z_next + *delta;
+                // Stop before any probe escapes or would need rebasing
+                valid &= (next.evaluate(u) - *delta).norm() <= SERIES_TOLERANCE * delta.norm()
+                    && full.norm_sqr() < 4.0
+                    && full.norm_sqr() > delta.norm_sqr();
+            }
+            if !valid {
+                break;
+            }
+            approximation = next;
+        }
+        approximation
+    }
+
+    /// Escape-time iteration of the pixel at `pixel_offset` from the view center,
+    /// starting after the iterations skipped by `series` when given.
+    pub fn iterate(
+        &self,
+        pixel_offset: (f64, f64),
+        max_iter: i32,
+        series: Option<&SeriesApproximation>,
+        mut tracking: OrbitTracking,
+    ) -> PerturbedPixel {
+        let delta_x = pixel_offset.0 - self.offset.0;
+        let delta_y = pixel_offset.1 - self.offset.1;
+        let (mut dx, mut dy, dcx, dcy) = match self.fractal_type {
+            // Mandelbrot: both orbits start at 0, the pixel differs in c
+            FractalType::Mandelbrot => (0.0, 0.0, delta_x, delta_y),
+            // Julia: c is shared, the pixel differs in the starting point
+            _ => (delta_x, delta_y, 0.0, 0.0),
+        };
+        // The pixel's own c, rounded to f64 it is only good for the plain f64 parts
+        let c = (self.c.0 + dcx, self.c.1 + dcy);
+        let mut n = 0;
+        if let Some(series) = series {
+            let u = Complex64::new(delta_x, delta_y);
+            let delta = series.evaluate(u);
+            dx = delta.re;
+            dy = delta.im;
+            n = series.skip;
+            let derivative = series.derivative(u);
+            tracking.set_derivative((derivative.re, derivative.im));
+        }
+
+        let last = self.orbit.len() - 1;
+        let mut iter = n as i32;
+        while iter < max_iter {
+            let (zr, zi) = self.orbit[n];
+            tracking.step((zr + dx, zi + dy));
+            let next_dx = 2.0 * (zr * dx - zi * dy) + dx * dx - dy * dy + dcx;
+            let next_dy = 2.0 * (zr * dy + zi * dx) + 2.0 * dx * dy + dcy;
+            dx = next_dx;
+            dy = next_dy;
+            n += 1;
+            iter += 1;
+
+            let (zr, zi) = self.orbit[n];
+            let (x, y) = (zr + dx, zi + dy);
+            tracking.visit((x, y), iter);
+            let mag = x * x + y * y;
+            if mag >= BAILOUT_SQ {
+                break;
+            }
+
+            let glitched = mag < GLITCH_TOLERANCE * (zr * zr + zi * zi);
+            let exhausted = n == last && iter < max_iter;
+            match self.fractal_type {
+                FractalType::Mandelbrot => {
+                    // Rebase onto the start of the reference orbit (Z_0 = 0): the full
+                    // value becomes the new delta, which keeps it small and precise.
+                    // Also done whenever |z| < |δ| so glitches never get a chance to form.
+                    if glitched || exhausted || mag < dx * dx + dy * dy {
+                        dx = x;
+                        dy = y;
+                        n = 0;
+                    }
+                }
+                _ => {
+                    // Julia orbits don't pass through a common point to rebase onto,
+                    // the pixel has to be redone against a different reference
+                    if glitched {
+                        return PerturbedPixel { sample: tracking.finish(iter, (x, y), c), glitched: true };
+                    }
+                    if exhausted {
+                        // The reference escaped first, but by now δ is no longer tiny
+                        // compared to z so plain f64 iteration is accurate again
+                        let sample = escape_time_f64((x, y), c, tracking, iter, max_iter);
+                        return PerturbedPixel { sample, glitched: false };
+                    }
+                }
+            }
+        }
+        let (zr, zi) = self.orbit[n];
+        PerturbedPixel { sample: tracking.finish(iter, (zr + dx, zi + dy), c), glitched: false }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Escape time of the Mandelbrot point `c` iterated directly in `BigFixed`.
+    fn direct_escape_time(c: (&BigFixed, &BigFixed), max_iter: i32) -> i32 {
+        let (mut zx, mut zy) = (c.0.clone(), c.1.clone());
+        for iter in 1..max_iter {
+            let xy = &zx * &zy;
+            (zx, zy) = (&(&zx.square() - &zy.square()) + c.0, &(&xy + &xy) + c.1);
+            let (x, y) = (zx.to_f64(), zy.to_f64());
+            if x * x + y * y >= BAILOUT_SQ {
+                return iter + 1;
+            }
+        }
+        max_iter
+    }
+
+    #[test]
+    fn matches_direct_iteration() {
+        // Plain f64 iteration already drifts off here, iterate directly with
+        // far more bits than the zoom needs instead
+        let zoom = 1e6;
+        let frac_bits = BigFixed::precision_for_zoom(zoom) * 4;
+        let center_x = BigFixed::parse("-0.743643887037151", frac_bits).unwrap();
+        let center_y = BigFixed::parse("0.131825904205330", frac_bits).unwrap();
+        let max_iter = 1000;
+        let cancel = CancelToken::new(Arc::new(AtomicU64::new(0)), 0);
+        let reference =
+            ReferenceOrbit::compute(FractalType::Mandelbrot, (&center_x, &center_y), (0.0, 0.0), (0.0, 0.0), max_iter, &cancel)
+                .unwrap();
+
+        let (span, steps) = (3.0 / zoom, 12);
+        let tracking = OrbitTracking::new(FractalType::Mandelbrot, span / steps as f64, false, None, false);
+        for i in 0..=steps {
+            for j in 0..=steps {
+                let offset = ((i as f64 / steps as f64 - 0.5) * span, (j as f64 / steps as f64 - 0.5) * span);
+                let perturbed = reference.iterate(offset, max_iter, None, tracking);
+                let direct = direct_escape_time((&center_x.add_f64(offset.0), &center_y.add_f64(offset.1)), max_iter);
+                assert!(!perturbed.glitched);
+                assert_eq!(perturbed.sample.iter, direct, "offset {offset:?}");
+            }
+        }
+    }
+}
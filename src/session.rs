@@ -0,0 +1,251 @@
+use std::path::Path;
+
+use toml_edit::{value, Array, Document, Item, Table};
+
+use crate::bigfixed::BigFixed;
+use crate::coloring::{ColorSettings, ColoringMode, InteriorMode, Lighting};
+use crate::fractal::{FractalType, MaxIterMode, MaxIterPolicy};
+use crate::location::Location;
+use crate::orbit_trap::{OrbitTrap, TrapShape};
+use crate::palette::{Interpolation, Palette, RepeatMode};
+
+/// Everything needed to pick up exploring where it was left: the location,
+/// how it is iterated and how it is colored. Saved as a TOML location file.
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// The view, the iteration limit it was last rendered with and the palette.
+    pub location: Location,
+    pub max_iter_policy: MaxIterPolicy,
+    /// Coloring, with the location's palette.
+    pub colors: ColorSettings,
+    pub orbit_trap: OrbitTrap,
+}
+
+impl Session {
+    /// The location file, with the center written as strings holding every
+    /// digit its precision resolves.
+    pub fn to_toml(&self) -> String {
+        let location = &self.location;
+        let mut doc = Document::new();
+        doc["fractal"] = value(location.fractal_type.as_str());
+
+        let mut view = Table::new();
+        view["center_x"] = value(location.center_x.to_string());
+        view["center_y"] = value(location.center_y.to_string());
+        view["zoom"] = value(location.zoom);
+        view["julia_c"] = value(Array::from_iter([location.julia_c.0, location.julia_c.1]));
+        doc["view"] = Item::Table(view);
+
+        let policy = &self.max_iter_policy;
+        let mut iterations = Table::new();
+        iterations["mode"] = value(policy.mode.as_str());
+        iterations["base"] = value(policy.base);
+        iterations["per_decade"] = value(policy.per_decade);
+        iterations["fixed"] = value(i64::from(policy.fixed));
+        iterations["boundary_percent"] = value(policy.boundary_percent);
+        iterations["limit"] = value(i64::from(location.max_iter));
+        doc["iterations"] = Item::Table(iterations);
+
+        let colors = &self.colors;
+        let mut coloring = Table::new();
+        coloring["mode"] = value(colors.mode.as_str());
+        coloring["gamma"] = value(colors.gamma);
+        coloring["line_thickness"] = value(colors.line_thickness);
+        coloring["interior"] = value(colors.interior.as_str());
+
+        let mut lighting = Table::new();
+        lighting["enabled"] = value(colors.lighting.enabled);
+        lighting["azimuth"] = value(colors.lighting.azimuth);
+        lighting["elevation"] = value(colors.lighting.elevation);
+        lighting["height"] = value(colors.lighting.height);
+        coloring["lighting"] = Item::Table(lighting);
+
+        let trap = &self.orbit_trap;
+        let mut orbit_trap = Table::new();
+        orbit_trap["shape"] = value(trap.shape.as_str());
+        orbit_trap["center"] = value(Array::from_iter([trap.center.0, trap.center.1]));
+        orbit_trap["angle"] = value(trap.angle);
+        orbit_trap["radius"] = value(trap.radius);
+        orbit_trap["size"] = value(trap.size);
+        coloring["orbit_trap"] = Item::Table(orbit_trap);
+
+        let palette = &location.palette;
+        let mut palette_table = Table::new();
+        palette_table["name"] = value(palette.name.as_str());
+        palette_table["interpolation"] = value(palette.interpolation.as_str());
+        palette_table["repeat"] = value(palette.repeat.as_str());
+        palette_table["offset"] = value(short_f64(palette.offset));
+        palette_table["scale"] = value(short_f64(palette.scale));
+        palette_table["stops"] = value(palette.stops_text());
+        coloring["palette"] = Item::Table(palette_table);
+        doc["coloring"] = Item::Table(coloring);
+
+        doc.to_string()
+    }
+
+    /// Inverse of `to_toml`. Only the fractal and the view are required,
+    /// settings left out fall back to their defaults for the fractal.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let doc: Document = text.parse().map_err(|err: toml_edit::TomlError| err.to_string())?;
+        let fields = Fields(doc.as_item());
+
+        let fractal_type = fields.require("fractal", |item| named(item, &FractalType::ALL, FractalType::as_str))?;
+        let zoom = fields.require("view.zoom", |item| float(item).filter(|&zoom| Location::valid_zoom(zoom)))?;
+        let frac_bits = BigFixed::precision_for_zoom(zoom);
+        let center = |path| fields.require(path, |item| BigFixed::parse(item.as_str()?, frac_bits));
+        let (center_x, center_y) = (center("view.center_x")?, center("view.center_y")?);
+        let julia_c = fields.get("view.julia_c", pair)?.unwrap_or((-0.7269, 0.1889));
+
+        let default = MaxIterPolicy::default();
+        let max_iter_policy = MaxIterPolicy {
+            mode: fields.get("iterations.mode", |item| named(item, &MaxIterMode::ALL, MaxIterMode::as_str))?.unwrap_or(default.mode),
+            base: fields.get("iterations.base", float)?.unwrap_or(default.base),
+            per_decade: fields.get("iterations.per_decade", float)?.unwrap_or(default.per_decade),
+            fixed: fields.get("iterations.fixed", |item| i32::try_from(item.as_integer()?).ok().filter(|fixed| *fixed >= 1))?.unwrap_or(default.fixed),
+            boundary_percent: fields.get("iterations.boundary_percent", float)?.unwrap_or(default.boundary_percent),
+        };
+
+        let default = fractal_type.default_colors();
+        let lighting = default.lighting;
+        let palette = match fields.get("coloring.palette", Item::as_table_like)? {
+            Some(_) => Palette {
+                name: fields.get("coloring.palette.name", |item| item.as_str().map(str::to_owned))?.unwrap_or_else(|| "Custom".to_owned()),
+                stops: fields.require("coloring.palette.stops", |item| Palette::parse_stops(item.as_str()?))?,
+                interpolation: fields.get("coloring.palette.interpolation", |item| named(item, &Interpolation::ALL, Interpolation::as_str))?.unwrap_or(default.palette.interpolation),
+                repeat: fields.get("coloring.palette.repeat", |item| named(item, &RepeatMode::ALL, RepeatMode::as_str))?.unwrap_or(default.palette.repeat),
+                offset: fields.get("coloring.palette.offset", float)?.map_or(default.palette.offset, |offset| offset as f32),
+                scale: fields.get("coloring.palette.scale", float)?.map_or(default.palette.scale, |scale| scale as f32),
+            },
+            None => default.palette,
+        };
+        let colors = ColorSettings {
+            mode: fields.get("coloring.mode", |item| named(item, &ColoringMode::ALL, ColoringMode::as_str))?.unwrap_or(default.mode),
+            palette: palette.clone(),
+            gamma: fields.get("coloring.gamma", float)?.unwrap_or(default.gamma),
+            line_thickness: fields.get("coloring.line_thickness", float)?.unwrap_or(default.line_thickness),
+            lighting: Lighting {
+                enabled: fields.get("coloring.lighting.enabled", Item::as_bool)?.unwrap_or(lighting.enabled),
+                azimuth: fields.get("coloring.lighting.azimuth", float)?.unwrap_or(lighting.azimuth),
+                elevation: fields.get("coloring.lighting.elevation", float)?.unwrap_or(lighting.elevation),
+                height: fields.get("coloring.lighting.height", float)?.unwrap_or(lighting.height),
+            },
+            interior: fields.get("coloring.interior", |item| named(item, &InteriorMode::ALL, InteriorMode::as_str))?.unwrap_or(default.interior),
+        };
+
+        let trap = OrbitTrap::default();
+        let orbit_trap = OrbitTrap {
+            shape: fields.get("coloring.orbit_trap.shape", |item| named(item, &TrapShape::ALL, TrapShape::as_str))?.unwrap_or(trap.shape),
+            center: fields.get("coloring.orbit_trap.center", pair)?.unwrap_or(trap.center),
+            angle: fields.get("coloring.orbit_trap.angle", float)?.unwrap_or(trap.angle),
+            radius: fields.get("coloring.orbit_trap.radius", float)?.unwrap_or(trap.radius),
+            size: fields.get("coloring.orbit_trap.size", float)?.unwrap_or(trap.size),
+        };
+
+        let max_iter = fields.get("iterations.limit", |item| i32::try_from(item.as_integer()?).ok().filter(|limit| *limit >= 1))?;
+        let location = Location {
+            fractal_type,
+            center_x,
+            center_y,
+            zoom,
+            julia_c,
+            max_iter: max_iter.unwrap_or(max_iter_policy.fixed),
+            palette,
+        };
+        Ok(Self { location, max_iter_policy, colors, orbit_trap })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_toml()).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        Self::from_toml(&text).map_err(|err| format!("{}: {err}", path.display()))
+    }
+}
+
+/// Values of a parsed document looked up by dotted path.
+struct Fields<'a>(&'a Item);
+
+impl Fields<'_> {
+    /// The converted value at `path`, `None` if it is missing and an error
+    /// naming the path if it can't be converted.
+    fn get<'a, T>(&'a self, path: &str, convert: impl FnOnce(&'a Item) -> Option<T>) -> Result<Option<T>, String> {
+        match path.split('.').try_fold(self.0, |item, key| item.get(key)) {
+            Some(item) => convert(item).map(Some).ok_or_else(|| format!("Invalid {path}")),
+            None => Ok(None),
+        }
+    }
+
+    fn require<'a, T>(&'a self, path: &str, convert: impl FnOnce(&'a Item) -> Option<T>) -> Result<T, String> {
+        self.get(path, convert)?.ok_or_else(|| format!("Missing {path}"))
+    }
+}
+
+/// The `f64` closest to the shortest decimal of `value`, so the file says 0.2
+/// rather than 0.20000000298023224. Reads back to the same `f32`.
+fn short_f64(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(f64::from(value))
+}
+
+/// Floats may be written without a fraction, which TOML reads as an integer.
+fn float(item: &Item) -> Option<f64> {
+    item.as_float().or_else(|| item.as_integer().map(|integer| integer as f64))
+}
+
+fn pair(item: &Item) -> Option<(f64, f64)> {
+    let array = item.as_array()?;
+    let mut numbers = array.iter().map(|value| value.as_float().or_else(|| value.as_integer().map(|integer| integer as f64)));
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(Some(a)), Some(Some(b)), None) => Some((a, b)),
+        _ => None,
+    }
+}
+
+/// The variant of `all` whose display name is the item's string.
+fn named<T: Copy>(item: &Item, all: &[T], name: fn(&T) -> &'static str) -> Option<T> {
+    let value = item.as_str()?;
+    all.iter().copied().find(|variant| name(variant) == value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_round_trip() {
+        let frac_bits = BigFixed::precision_for_zoom(1e40);
+        let mut colors = FractalType::Mandelbrot.default_colors();
+        colors.mode = ColoringMode::Histogram;
+        colors.lighting.enabled = true;
+        colors.interior = InteriorMode::AtomDomain;
+        colors.palette = Palette { offset: 0.3, scale: 1.7, ..Palette::ocean() };
+        let session = Session {
+            location: Location {
+                fractal_type: FractalType::Mandelbrot,
+                center_x: BigFixed::parse("-0.74364388703715870475219150611477", frac_bits).unwrap(),
+                center_y: BigFixed::parse("0.13182590420531197049313205638631", frac_bits).unwrap(),
+                zoom: 1e40,
+                julia_c: (-0.7269, 0.1889),
+                max_iter: 54321,
+                palette: colors.palette.clone(),
+            },
+            max_iter_policy: MaxIterPolicy { mode: MaxIterMode::Adaptive, fixed: 5000, ..MaxIterPolicy::default() },
+            colors,
+            orbit_trap: OrbitTrap { shape: TrapShape::Cross, angle: 30.0, ..OrbitTrap::default() },
+        };
+        let read = Session::from_toml(&session.to_toml()).unwrap();
+
+        let (location, read_location) = (&session.location, &read.location);
+        assert_eq!(read_location.fractal_type, location.fractal_type);
+        assert_eq!(read_location.center_x, location.center_x);
+        assert_eq!(read_location.center_y, location.center_y);
+        assert_eq!(read_location.zoom, location.zoom);
+        assert_eq!(read_location.julia_c, location.julia_c);
+        assert_eq!(read_location.max_iter, location.max_iter);
+        assert_eq!(read_location.palette, location.palette);
+        assert_eq!(read.max_iter_policy, session.max_iter_policy);
+        assert_eq!(read.colors, session.colors);
+        assert_eq!(read.orbit_trap, session.orbit_trap);
+    }
+}
@@ -0,0 +1,176 @@
+use rayon::prelude::*;
+
+use crate::fractal::{CancelToken, Sample};
+
+/// Height of the bands the frame is split into, each band is subdivided on its own thread.
+const BAND_HEIGHT: usize = 32;
+
+/// Rectangles this small or smaller are computed pixel by pixel, their
+/// border would be most of their area anyway.
+const MIN_SIZE: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pixel {
+    Pending,
+    Computed,
+    /// Computed, but the perturbation glitched, it gets redone later.
+    Glitched,
+}
+
+/// Which rectangles with a uniform border may be filled instead of iterated.
+#[derive(Clone, Copy)]
+pub struct Fill {
+    /// Rectangles inside the set. Off when interior pixels are colored by
+    /// values of their own, which a uniform iteration count says nothing about.
+    pub inside: bool,
+    /// Rectangles outside the set.
+    pub outside: bool,
+    /// Only fill rectangles inside the set if their center is inside too.
+    pub check_center: bool,
+}
+
+/// What a subdivided frame left to do.
+pub struct Subdivided {
+    /// Pixels that have to be redone against another reference orbit.
+    pub glitched: Vec<(usize, usize)>,
+    /// Pixels filled in from their rectangle's border instead of being iterated.
+    pub filled: usize,
+}
+
+/// Render a frame by Mariani-Silver subdivision.
+///
+/// Computes the border of a rectangle and, if every border pixel came out with
+/// the same iteration count, fills the inside with the border's sample.
+/// Otherwise the rectangle is split in two along its longer side and both
+/// halves get the same treatment. The set has no holes, so a border that stays
+/// inside it encloses nothing but more of the set, as far as the pixels can tell.
+///
+/// Filling rectangles outside the set flattens the smooth coloring of escaped
+/// bands, `fill` says which rectangles are fair game.
+///
+/// `compute` iterates the pixel at (x, y), returning its sample and whether it glitched.
+pub fn render(
+    samples: &mut [Sample],
+    width: usize,
+    max_iter: i32,
+    fill: Fill,
+    cancel: &CancelToken,
+    compute: impl Fn(usize, usize) -> (Sample, bool) + Sync,
+) -> Option<Subdivided> {
+    let bands: Vec<_> = samples
+        .par_chunks_mut(width * BAND_HEIGHT)
+        .enumerate()
+        .map(|(band, samples)| {
+            let height = samples.len() / width;
+            let mut band = Band {
+                samples,
+                state: vec![Pixel::Pending; width * height],
+                width,
+                top: band * BAND_HEIGHT,
+                max_iter,
+                fill,
+                cancel,
+                compute: &compute,
+                filled: 0,
+            };
+            band.subdivide(0, 0, width - 1, height - 1);
+            band
+        })
+        .collect();
+    if cancel.is_cancelled() {
+        return None;
+    }
+
+    let mut result = Subdivided { glitched: Vec::new(), filled: 0 };
+    for band in bands {
+        result.filled += band.filled;
+        let glitched = band.state.iter().enumerate().filter(|&(_, &pixel)| pixel == Pixel::Glitched);
+        result.glitched.extend(glitched.map(|(i, _)| (i % width, band.top + i / width)));
+    }
+    Some(result)
+}
+
+/// A horizontal strip of the frame, coordinates inside it are relative to its top.
+struct Band<'a, F> {
+    samples: &'a mut [Sample],
+    state: Vec<Pixel>,
+    width: usize,
+    top: usize,
+    max_iter: i32,
+    fill: Fill,
+    cancel: &'a CancelToken,
+    compute: &'a F,
+    filled: usize,
+}
+
+impl<F: Fn(usize, usize) -> (Sample, bool)> Band<'_, F> {
+    /// Sample at (x, y), computed on first use. Neighbouring rectangles share
+    /// their edges, so most border pixels are asked for twice.
+    fn sample(&mut self, x: usize, y: usize) -> (Sample, bool) {
+        let i = y * self.width + x;
+        if self.state[i] == Pixel::Pending {
+            let (sample, glitched) = (self.compute)(x, self.top + y);
+            self.samples[i] = sample;
+            self.state[i] = if glitched { Pixel::Glitched } else { Pixel::Computed };
+        }
+        (self.samples[i], self.state[i] == Pixel::Glitched)
+    }
+
+    /// Work through the rectangle with inclusive corners (x0, y0) and (x1, y1).
+    fn subdivide(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        if x1 - x0 < MIN_SIZE || y1 - y0 < MIN_SIZE {
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    self.sample(x, y);
+                }
+            }
+            return;
+        }
+
+        // A glitched pixel's iteration count can't be trusted, so it breaks uniformity
+        let (first, _) = self.sample(x0, y0);
+        let mut uniform = true;
+        let top_bottom = (x0..=x1).flat_map(|x| [(x, y0), (x, y1)]);
+        let left_right = (y0 + 1..y1).flat_map(|y| [(x0, y), (x1, y)]);
+        for (x, y) in top_bottom.chain(left_right) {
+            let (sample, glitched) = self.sample(x, y);
+            uniform &= !glitched && sample.iter == first.iter;
+        }
+        if uniform {
+            uniform = if first.iter == self.max_iter {
+                self.fill.inside
+                    && (!self.fill.check_center || {
+                        let (center, glitched) = self.sample((x0 + x1) / 2, (y0 + y1) / 2);
+                        !glitched && center.iter == self.max_iter
+                    })
+            } else {
+                self.fill.outside
+            };
+        }
+
+        if uniform {
+            let fill = Sample { short_circuited: false, ..first };
+            for y in y0 + 1..y1 {
+                for x in x0 + 1..x1 {
+                    let i = y * self.width + x;
+                    if self.state[i] == Pixel::Pending {
+                        self.samples[i] = fill;
+                        self.state[i] = Pixel::Computed;
+                        self.filled += 1;
+                    }
+                }
+            }
+        } else if x1 - x0 >= y1 - y0 {
+            let mid = (x0 + x1) / 2;
+            self.subdivide(x0, y0, mid, y1);
+            self.subdivide(mid, y0, x1, y1);
+        } else {
+            let mid = (y0 + y1) / 2;
+            self.subdivide(x0, y0, x1, mid);
+            self.subdivide(x0, mid, x1, y1);
+        }
+    }
+}
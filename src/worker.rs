@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::fractal::{CancelToken, FractalView, RenderStats, RenderedFrame};
+
+struct RenderJob {
+    generation: u64,
+    view: FractalView,
+    threads: usize,
+}
+
+/// Frame sent back from the render thread, either a coarse preview pass or the finished render.
+pub struct RenderResult {
+    generation: u64,
+    pub frame: RenderedFrame,
+    /// The view the frame was rendered for.
+    pub view: FractalView,
+    pub elapsed: Duration,
+    pub complete: bool,
+    pub stats: RenderStats,
+}
+
+/// Number of threads to render with when the user did not pick one.
+pub fn default_thread_count() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Background thread that renders fractal views so the UI thread never blocks
+/// on the escape-time loop.
+///
+/// Every request bumps a shared generation counter. A render in flight polls
+/// the counter and gives up as soon as a newer view has been requested, so only
+/// the latest view is ever finished.
+pub struct RenderWorker {
+    jobs: Sender<RenderJob>,
+    results: Receiver<RenderResult>,
+    latest_generation: Arc<AtomicU64>,
+    delivered_generation: u64,
+}
+
+impl RenderWorker {
+    pub fn spawn(ctx: egui::Context) -> Self {
+        let (job_tx, job_rx) = mpsc::channel::<RenderJob>();
+        let (result_tx, result_rx) = mpsc::channel();
+        let latest_generation = Arc::new(AtomicU64::new(0));
+
+        let latest = latest_generation.clone();
+        thread::Builder::new()
+            .name("fractal-render".to_owned())
+            .spawn(move || {
+                let mut pool: Option<rayon::ThreadPool> = None;
+                while let Ok(job) = job_rx.recv() {
+                    // Skip over views that were superseded while we were busy
+                    let job = job_rx.try_iter().last().unwrap_or(job);
+
+                    // Rebuild the pool only when the requested thread count changes
+                    if pool.as_ref().is_none_or(|pool| pool.current_num_threads() != job.threads) {
+                        pool = Some(
+                            rayon::ThreadPoolBuilder::new()
+                                .num_threads(job.threads)
+                                .thread_name(|i| format!("fractal-render-{i}"))
+                                .build()
+                                .expect("failed to build render thread pool"),
+                        );
+                    }
+                    let pool = pool.as_ref().unwrap();
+
+                    let cancel = CancelToken::new(latest.clone(), job.generation);
+                    let started = Instant::now();
+                    let on_preview = |frame| {
+                        let _ = result_tx.send(RenderResult {
+                            generation: job.generation,
+                            frame,
+                            view: job.view.clone(),
+                            elapsed: started.elapsed(),
+                            complete: false,
+                            stats: RenderStats::default(),
+                        });
+                        ctx.request_repaint();
+                    };
+                    let Some((frame, stats)) = pool.install(|| job.view.generate_fractal_image(&cancel, on_preview)) else {
+                        continue;
+                    };
+                    let result = RenderResult {
+                        generation: job.generation,
+                        frame,
+                        view: job.view,
+                        elapsed: started.elapsed(),
+                        complete: true,
+                        stats,
+                    };
+                    if result_tx.send(result).is_err() {
+                        // The UI side is gone, nothing left to render for
+                        break;
+                    }
+                    // Wake up the UI so it picks up the new frame
+                    ctx.request_repaint();
+                }
+            })
+            .expect("failed to spawn render thread");
+
+        Self {
+            jobs: job_tx,
+            results: result_rx,
+            latest_generation,
+            delivered_generation: 0,
+        }
+    }
+
+    /// Queue a view for rendering on `threads` threads, cancelling any render still in progress.
+    pub fn request(&mut self, view: FractalView, threads: usize) {
+        let generation = self.latest_generation.fetch_add(1, Ordering::Relaxed) + 1;
+        let _ = self.jobs.send(RenderJob { generation, view, threads: threads.max(1) });
+    }
+
+    /// Return the most recent frame, if any arrived since the last call.
+    pub fn latest(&mut self) -> Option<RenderResult> {
+        let result = self.results.try_iter().last()?;
+        if result.complete {
+            self.delivered_generation = result.generation;
+        }
+        Some(result)
+    }
+
+    /// True while the last requested view has not been delivered yet.
+    pub fn is_busy(&self) -> bool {
+        self.delivered_generation < self.latest_generation.load(Ordering::Relaxed)
+    }
+}